@@ -0,0 +1,274 @@
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::{AnimationDecoder, Frames, ImageError, RgbaImage};
+use crate::atlas::{AtlasBuilder, AtlasRegion};
+use crate::graphics::{Texture, TextureParams};
+use crate::sprite::TextureRegion;
+
+/// Frame time used for GIF frames with a (near) zero delay, as browsers do
+const DEFAULT_DELAY: f32 = 0.1;
+
+#[derive(Debug)]
+pub enum AnimationError {
+    Io(PathBuf, std::io::Error),
+    Decode(PathBuf, ImageError),
+    /// The file or sequence contained no frames
+    Empty,
+}
+impl Display for AnimationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnimationError::Io(path, err) => write!(f, "Error reading animation {:?}: {}", path, err),
+            AnimationError::Decode(path, err) => write!(f, "Error decoding animation {:?}: {}", path, err),
+            AnimationError::Empty => write!(f, "Animation has no frames"),
+        }
+    }
+}
+impl std::error::Error for AnimationError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayMode {
+    Loop,
+    /// Stops on the last frame
+    Once,
+    /// Plays forward then backward, repeatedly
+    PingPong,
+}
+
+/// All frames of an animation packed into an atlas, along with how long each one is shown.
+///
+/// Call `update` once per frame with the elapsed time, then draw `region()` of `texture()`.
+pub struct AnimatedTexture {
+    atlas: AtlasBuilder,
+    frames: Vec<AtlasRegion>,
+    /// Frame durations in seconds
+    delays: Vec<f32>,
+    pub mode: PlayMode,
+    pub playing: bool,
+    current: usize,
+    time: f32,
+    reverse: bool,
+}
+impl AnimatedTexture {
+    /// Loads an animated GIF or PNG (APNG). Other images are loaded as a single frame.
+    pub fn from_path(path: &Path) -> Result<Self, AnimationError> {
+        Self::from_path_with(path, TextureParams::default())
+    }
+    
+    pub fn from_path_with(path: &Path, params: TextureParams) -> Result<Self, AnimationError> {
+        let open = || File::open(path).map(BufReader::new).map_err(|err| AnimationError::Io(path.to_path_buf(), err));
+        let decode_err = |err| AnimationError::Decode(path.to_path_buf(), err);
+        
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_ascii_lowercase();
+        let frames = match extension.as_str() {
+            "gif" => collect_frames(GifDecoder::new(open()?).map_err(decode_err)?.into_frames()).map_err(decode_err)?,
+            "png" | "apng" => {
+                let decoder = PngDecoder::new(open()?).map_err(decode_err)?;
+                if decoder.is_apng() {
+                    collect_frames(decoder.apng().into_frames()).map_err(decode_err)?
+                } else {
+                    vec![(image::open(path).map_err(decode_err)?.into_rgba8(), DEFAULT_DELAY)]
+                }
+            },
+            _ => vec![(image::open(path).map_err(decode_err)?.into_rgba8(), DEFAULT_DELAY)],
+        };
+        
+        Self::from_frames(frames, params)
+    }
+    
+    /// Loads one image per frame, each shown for `frame_time` seconds.
+    pub fn from_sequence(paths: &[PathBuf], frame_time: f32, params: TextureParams) -> Result<Self, AnimationError> {
+        let mut frames = Vec::with_capacity(paths.len());
+        for path in paths {
+            let img = image::open(path).map_err(|err| AnimationError::Decode(path.clone(), err))?;
+            frames.push((img.into_rgba8(), frame_time));
+        }
+        
+        Self::from_frames(frames, params)
+    }
+    
+    /// Loads a numbered image sequence from `dir`, e.g. `explosion_1.png`, `explosion_2.png`, ... for
+    /// `prefix = "explosion_"` and `extension = "png"`. Frames are ordered by number, with or without zero padding.
+    pub fn from_numbered(dir: &Path, prefix: &str, extension: &str, frame_time: f32, params: TextureParams) -> Result<Self, AnimationError> {
+        let entries = std::fs::read_dir(dir).map_err(|err| AnimationError::Io(dir.to_path_buf(), err))?;
+        
+        let mut numbered = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|err| AnimationError::Io(dir.to_path_buf(), err))?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(extension) {
+                continue;
+            }
+            let number = path.file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.strip_prefix(prefix))
+                .and_then(|digits| digits.parse::<u32>().ok());
+            if let Some(number) = number {
+                numbered.push((number, path));
+            }
+        }
+        numbered.sort();
+        
+        let paths: Vec<PathBuf> = numbered.into_iter().map(|(_, path)| path).collect();
+        Self::from_sequence(&paths, frame_time, params)
+    }
+    
+    /// Packs already decoded frames, given with their duration in seconds. Zero durations are replaced with 0.1s.
+    pub fn from_frames(frames: Vec<(RgbaImage, f32)>, params: TextureParams) -> Result<Self, AnimationError> {
+        if frames.is_empty() {
+            return Err(AnimationError::Empty);
+        }
+        
+        let mut max_size = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max_size);
+        }
+        
+        // a roughly square grid of the largest frame, so most animations fit in a single page
+        let frame_width = frames.iter().map(|(img, _)| img.width()).max().unwrap() + 1;
+        let frame_height = frames.iter().map(|(img, _)| img.height()).max().unwrap() + 1;
+        let columns = (frames.len() as f32).sqrt().ceil() as u32;
+        let rows = (frames.len() as u32).div_ceil(columns);
+        let page_width = (columns * frame_width).min(max_size as u32);
+        let page_height = (rows * frame_height).min(max_size as u32);
+        
+        let mut atlas = AtlasBuilder::new(page_width, page_height).with_params(params);
+        let (images, mut delays): (Vec<RgbaImage>, Vec<f32>) = frames.into_iter().unzip();
+        for delay in &mut delays {
+            if *delay <= 0.0 {
+                *delay = DEFAULT_DELAY;
+            }
+        }
+        let regions = atlas.add_all(&images);
+        
+        Ok(Self {
+            atlas,
+            frames: regions,
+            delays,
+            mode: PlayMode::Loop,
+            playing: true,
+            current: 0,
+            time: 0.0,
+            reverse: false,
+        })
+    }
+    
+    pub fn with_mode(mut self, mode: PlayMode) -> Self {
+        self.mode = mode;
+        
+        self
+    }
+    
+    /// Advances the animation by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        if !self.playing || self.frames.len() < 2 {
+            return;
+        }
+        
+        self.time += dt;
+        while self.time >= self.delays[self.current] {
+            self.time -= self.delays[self.current];
+            if !self.advance() {
+                self.time = 0.0;
+                self.playing = false;
+                break;
+            }
+        }
+    }
+    
+    /// Moves to the next frame according to `mode`, returns false once a `PlayMode::Once` animation ended.
+    fn advance(&mut self) -> bool {
+        let last = self.frames.len() - 1;
+        match self.mode {
+            PlayMode::Loop => self.current = (self.current + 1) % self.frames.len(),
+            PlayMode::Once => {
+                if self.current == last {
+                    return false;
+                }
+                self.current += 1;
+            },
+            PlayMode::PingPong => {
+                if (self.reverse && self.current == 0) || (!self.reverse && self.current == last) {
+                    self.reverse = !self.reverse;
+                }
+                if self.reverse {
+                    self.current -= 1;
+                } else {
+                    self.current += 1;
+                }
+            },
+        }
+        
+        true
+    }
+    
+    /// Restarts from the first frame
+    pub fn reset(&mut self) {
+        self.current = 0;
+        self.time = 0.0;
+        self.reverse = false;
+        self.playing = true;
+    }
+    
+    pub fn set_frame(&mut self, index: usize) {
+        self.current = index.min(self.frames.len() - 1);
+        self.time = 0.0;
+    }
+    
+    pub fn frame_index(&self) -> usize {
+        self.current
+    }
+    
+    /// Texture containing the current frame
+    pub fn texture(&self) -> &Texture {
+        self.atlas.page(self.frames[self.current].page)
+    }
+    
+    /// Region of `texture()` holding the current frame
+    pub fn region(&self) -> &TextureRegion {
+        &self.frames[self.current].region
+    }
+    
+    pub fn frame(&self, index: usize) -> &AtlasRegion {
+        &self.frames[index]
+    }
+    
+    /// Duration of a frame in seconds
+    pub fn delay(&self, index: usize) -> f32 {
+        self.delays[index]
+    }
+    
+    /// Length of a single pass through all frames, in seconds
+    pub fn duration(&self) -> f32 {
+        self.delays.iter().sum()
+    }
+    
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+    
+    /// Whether a `PlayMode::Once` animation reached its last frame
+    pub fn is_finished(&self) -> bool {
+        self.mode == PlayMode::Once && !self.playing && self.current == self.frames.len() - 1
+    }
+}
+
+fn collect_frames(frames: Frames) -> Result<Vec<(RgbaImage, f32)>, ImageError> {
+    frames.map(|frame| {
+        let frame = frame?;
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        let mut delay = numer as f32 / denom.max(1) as f32 / 1000.0;
+        if delay < 0.011 {
+            delay = DEFAULT_DELAY;
+        }
+        
+        Ok((frame.into_buffer(), delay))
+    }).collect()
+}
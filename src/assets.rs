@@ -0,0 +1,364 @@
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use image::{ImageError, RgbaImage};
+use crate::font::BitmapFont;
+use crate::graphics::{ShaderProgram, Texture};
+
+#[derive(Debug)]
+pub enum AssetError {
+    Io(PathBuf, std::io::Error),
+    Image(PathBuf, ImageError),
+    /// The file was read but its contents are invalid
+    Invalid(PathBuf, String),
+}
+impl Display for AssetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssetError::Io(path, err) => write!(f, "Error reading {:?}: {}", path, err),
+            AssetError::Image(path, err) => write!(f, "Error decoding image {:?}: {}", path, err),
+            AssetError::Invalid(path, err) => write!(f, "Invalid asset {:?}: {}", path, err),
+        }
+    }
+}
+impl std::error::Error for AssetError {}
+
+fn read_string(path: &Path) -> Result<String, AssetError> {
+    std::fs::read_to_string(path).map_err(|err| AssetError::Io(path.to_path_buf(), err))
+}
+
+fn read_image(path: &Path) -> Result<RgbaImage, AssetError> {
+    Ok(image::open(path).map_err(|err| AssetError::Image(path.to_path_buf(), err))?.into_rgba8())
+}
+
+/// Something `Assets` can load. Loading is split in two: `decode` runs on a worker thread and must not
+/// touch GL, `upload` runs on the main thread with the GL context current.
+pub trait Asset: Sized + 'static {
+    type Data: Send + 'static;
+    
+    fn decode(path: &Path) -> Result<Self::Data, AssetError>;
+    fn upload(path: &Path, data: Self::Data) -> Result<Self, AssetError>;
+}
+
+impl Asset for Texture {
+    type Data = RgbaImage;
+    
+    fn decode(path: &Path) -> Result<RgbaImage, AssetError> {
+        read_image(path)
+    }
+    
+    fn upload(_path: &Path, data: RgbaImage) -> Result<Self, AssetError> {
+        Ok(Texture::from_image(data))
+    }
+}
+
+/// Loads a BMFont text file along with its first page texture.
+impl Asset for BitmapFont {
+    type Data = (String, RgbaImage);
+    
+    fn decode(path: &Path) -> Result<Self::Data, AssetError> {
+        let fnt = read_string(path)?;
+        let page = BitmapFont::page_file(&fnt).ok_or(AssetError::Invalid(path.to_path_buf(), "No texture page".to_owned()))?;
+        let img = read_image(&path.parent().unwrap_or(Path::new("")).join(page))?;
+        
+        Ok((fnt, img))
+    }
+    
+    fn upload(path: &Path, (fnt, img): Self::Data) -> Result<Self, AssetError> {
+        BitmapFont::try_new(Texture::from_image(img), &fnt).map_err(|err| AssetError::Invalid(path.to_path_buf(), err))
+    }
+}
+
+/// Loads a `.vert` file and the `.frag` file with the same name next to it.
+impl Asset for ShaderProgram {
+    type Data = (String, String);
+    
+    fn decode(path: &Path) -> Result<Self::Data, AssetError> {
+        Ok((read_string(path)?, read_string(&path.with_extension("frag"))?))
+    }
+    
+    fn upload(path: &Path, (vertex, fragment): Self::Data) -> Result<Self, AssetError> {
+        let mut shader = ShaderProgram::new();
+        shader.create_vertex_shader(&vertex);
+        shader.create_fragment_shader(&fragment);
+        shader.try_link().map_err(|err| AssetError::Invalid(path.to_path_buf(), err))?;
+        
+        Ok(shader)
+    }
+}
+
+enum Slot<T> {
+    Loading,
+    Loaded(Rc<T>),
+    Failed(Rc<AssetError>),
+}
+
+/// Shared reference to an asset which may still be loading.
+///
+/// Handles are refcounted: once every handle to an asset is dropped, `Assets::collect` unloads it.
+pub struct Handle<T> {
+    slot: Rc<RefCell<Slot<T>>>,
+}
+impl<T> Handle<T> {
+    /// The asset, if it finished loading
+    pub fn get(&self) -> Option<Rc<T>> {
+        match &*self.slot.borrow() {
+            Slot::Loaded(asset) => Some(asset.clone()),
+            _ => None,
+        }
+    }
+    
+    pub fn is_loading(&self) -> bool {
+        matches!(*self.slot.borrow(), Slot::Loading)
+    }
+    
+    pub fn is_loaded(&self) -> bool {
+        matches!(*self.slot.borrow(), Slot::Loaded(_))
+    }
+    
+    pub fn error(&self) -> Option<Rc<AssetError>> {
+        match &*self.slot.borrow() {
+            Slot::Failed(err) => Some(err.clone()),
+            _ => None,
+        }
+    }
+}
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+type Decoded = Result<Box<dyn Any + Send>, AssetError>;
+type Job = (u64, Box<dyn FnOnce() -> Decoded + Send>);
+
+/// `Rc<RefCell<Slot<T>>>` of the asset's type
+type Entry = Rc<dyn Any>;
+
+struct PendingUpload {
+    path: PathBuf,
+    upload: Box<dyn FnOnce(Decoded) -> Result<(), Rc<AssetError>>>,
+}
+
+/// Loads assets in the background and hands out `Handle`s to them.
+///
+/// Files are decoded on worker threads, then uploaded to GL in `update`, which should be called once per
+/// frame on the main thread. Loading the same path twice returns the same asset.
+pub struct Assets {
+    jobs: Sender<Job>,
+    results: Receiver<(u64, Decoded)>,
+    entries: HashMap<(TypeId, PathBuf), Entry>,
+    pending: HashMap<u64, PendingUpload>,
+    next_id: u64,
+    requested: usize,
+    finished: usize,
+    errors: Vec<(PathBuf, Rc<AssetError>)>,
+}
+impl Assets {
+    /// Starts `threads` decoding threads (at least one).
+    pub fn new(threads: usize) -> Self {
+        let (job_tx, job_rx) = channel::<Job>();
+        let (result_tx, result_rx) = channel();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+        
+        // workers exit when the job channel closes, or when sending a result fails once the manager is dropped
+        for _ in 0..threads.max(1) {
+            let jobs = job_rx.clone();
+            let results = result_tx.clone();
+            std::thread::spawn(move || loop {
+                let job = jobs.lock().unwrap().recv();
+                match job {
+                    Ok((id, decode)) => {
+                        if results.send((id, decode())).is_err() {
+                            break;
+                        }
+                    },
+                    Err(_) => break,
+                }
+            });
+        }
+        
+        Self {
+            jobs: job_tx,
+            results: result_rx,
+            entries: HashMap::new(),
+            pending: HashMap::new(),
+            next_id: 0,
+            requested: 0,
+            finished: 0,
+            errors: Vec::new(),
+        }
+    }
+    
+    /// Starts loading `path`, or returns the existing handle if it was already requested.
+    pub fn load<T: Asset>(&mut self, path: &Path) -> Handle<T> {
+        let key = (TypeId::of::<T>(), path.to_path_buf());
+        if let Some(entry) = self.entries.get(&key) {
+            return Handle {
+                slot: entry.clone().downcast::<RefCell<Slot<T>>>().unwrap(),
+            };
+        }
+        
+        let slot = Rc::new(RefCell::new(Slot::<T>::Loading));
+        self.entries.insert(key, slot.clone());
+        
+        let id = self.next_id;
+        self.next_id += 1;
+        
+        let decode_path = path.to_path_buf();
+        let decode = Box::new(move || {
+            // a panicking decoder would otherwise never send a result, leaving `finish` waiting forever
+            let decoded = catch_unwind(AssertUnwindSafe(|| T::decode(&decode_path)))
+                .unwrap_or_else(|_| Err(AssetError::Invalid(decode_path.clone(), "Decoder panicked".to_owned())));
+            
+            decoded.map(|data| Box::new(data) as Box<dyn Any + Send>)
+        });
+        
+        let upload_path = path.to_path_buf();
+        let upload_slot = slot.clone();
+        let upload = Box::new(move |decoded: Decoded| {
+            let result = decoded.and_then(|data| T::upload(&upload_path, *data.downcast::<T::Data>().unwrap()));
+            match result {
+                Ok(asset) => {
+                    *upload_slot.borrow_mut() = Slot::Loaded(Rc::new(asset));
+                    Ok(())
+                },
+                Err(err) => {
+                    let err = Rc::new(err);
+                    *upload_slot.borrow_mut() = Slot::Failed(err.clone());
+                    Err(err)
+                },
+            }
+        });
+        
+        self.pending.insert(id, PendingUpload { path: path.to_path_buf(), upload });
+        self.requested += 1;
+        if self.jobs.send((id, decode)).is_err() {
+            // every worker exited, e.g. after a panic while decoding
+            let err = std::io::Error::new(std::io::ErrorKind::BrokenPipe, "asset decoding threads stopped");
+            self.upload(id, Err(AssetError::Io(path.to_path_buf(), err)));
+        }
+        
+        Handle { slot }
+    }
+    
+    /// Uploads decoded assets until `budget` is spent. At least one asset is uploaded per call if any is ready.
+    pub fn update(&mut self, budget: Duration) {
+        let start = Instant::now();
+        while let Ok((id, decoded)) = self.results.try_recv() {
+            self.upload(id, decoded);
+            
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+    }
+    
+    /// Blocks until every requested asset is loaded or failed.
+    pub fn finish(&mut self) {
+        while !self.pending.is_empty() {
+            match self.results.recv() {
+                Ok((id, decoded)) => self.upload(id, decoded),
+                Err(_) => break,
+            }
+        }
+    }
+    
+    fn upload(&mut self, id: u64, decoded: Decoded) {
+        if let Some(pending) = self.pending.remove(&id) {
+            if let Err(err) = (pending.upload)(decoded) {
+                self.errors.push((pending.path, err));
+            }
+            self.finished += 1;
+        }
+    }
+    
+    /// Fraction of the assets requested so far that finished loading (or failed), 1 if none are pending.
+    /// The count restarts once everything is loaded.
+    pub fn progress(&mut self) -> f32 {
+        if self.pending.is_empty() {
+            self.requested = 0;
+            self.finished = 0;
+            
+            return 1.0;
+        }
+        
+        self.finished as f32 / self.requested as f32
+    }
+    
+    /// Number of assets still being decoded or waiting for upload
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+    
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+    
+    /// Returns and clears the errors of assets that failed to load since the last call.
+    pub fn take_errors(&mut self) -> Vec<(PathBuf, Rc<AssetError>)> {
+        std::mem::take(&mut self.errors)
+    }
+    
+    /// Forgets the asset at `path`, so the next `load` reads it again. Existing handles keep it alive until dropped.
+    pub fn unload<T: Asset>(&mut self, path: &Path) {
+        self.entries.remove(&(TypeId::of::<T>(), path.to_path_buf()));
+    }
+    
+    /// Unloads every asset without any remaining `Handle`, returns how many were unloaded. Assets still
+    /// loading are kept until they finish.
+    pub fn collect(&mut self) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| Rc::strong_count(entry) > 1);
+        
+        before - self.entries.len()
+    }
+    
+    /// Number of loaded or loading assets
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    struct Panics;
+    impl Asset for Panics {
+        type Data = ();
+        
+        fn decode(path: &Path) -> Result<(), AssetError> {
+            panic!("Malformed file {:?}", path);
+        }
+        
+        fn upload(_path: &Path, _data: ()) -> Result<Self, AssetError> {
+            Ok(Panics)
+        }
+    }
+    
+    #[test]
+    fn finishes_after_decoder_panic() {
+        let mut assets = Assets::new(2);
+        let handle = assets.load::<Panics>(Path::new("broken.bin"));
+        assets.finish();
+        
+        assert!(assets.is_done());
+        assert_eq!(assets.progress(), 1.0);
+        assert!(matches!(handle.error().as_deref(), Some(AssetError::Invalid(..))));
+        assert_eq!(assets.take_errors().len(), 1);
+    }
+}
@@ -0,0 +1,232 @@
+use image::RgbaImage;
+use crate::graphics::{Texture, TextureParams};
+use crate::sprite::TextureRegion;
+
+/// Location of an image packed by `AtlasBuilder`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasRegion {
+    /// Index of the page texture, see `AtlasBuilder::page`
+    pub page: usize,
+    pub region: TextureRegion,
+}
+
+/// Packs many small images into a few large textures so `TextureRenderer` can batch them.
+///
+/// Images are placed with a skyline (bottom-left) packer and uploaded as soon as they're added, so more
+/// images can be added at any time; a new page is started when an image doesn't fit in any existing one.
+/// Each image can be surrounded by copies of its edge pixels (`extrude`) and by empty space (`padding`),
+/// which prevents neighbouring images from bleeding in with linear filtering or mipmaps.
+pub struct AtlasBuilder {
+    page_width: u32,
+    page_height: u32,
+    padding: u32,
+    extrude: u32,
+    params: TextureParams,
+    pages: Vec<AtlasPage>,
+}
+impl AtlasBuilder {
+    pub fn new(page_width: u32, page_height: u32) -> Self {
+        Self {
+            page_width,
+            page_height,
+            padding: 1,
+            extrude: 0,
+            params: TextureParams::default(),
+            pages: Vec::new(),
+        }
+    }
+    
+    /// Empty pixels between packed images, 1 by default
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        
+        self
+    }
+    
+    /// Number of times the edge pixels of each image are repeated around it, 0 by default
+    pub fn with_extrude(mut self, extrude: u32) -> Self {
+        self.extrude = extrude;
+        
+        self
+    }
+    
+    /// Sampling parameters of the page textures, applied to pages created after this call.
+    pub fn with_params(mut self, params: TextureParams) -> Self {
+        self.params = params;
+        
+        self
+    }
+    
+    /// Packs and uploads `img`. Panics if it's larger than a page.
+    pub fn add(&mut self, img: &RgbaImage) -> AtlasRegion {
+        let (width, height) = img.dimensions();
+        if width == 0 || height == 0 {
+            panic!("Cannot add an empty {}x{} image to an atlas", width, height);
+        }
+        
+        let cell_width = width + self.extrude * 2 + self.padding;
+        let cell_height = height + self.extrude * 2 + self.padding;
+        if cell_width > self.page_width || cell_height > self.page_height {
+            panic!("Image of {}x{} (with padding and extrusion) doesn't fit in a {}x{} atlas page", cell_width, cell_height, self.page_width, self.page_height);
+        }
+        
+        let mut placed = self.pages.iter_mut().enumerate().find_map(|(i, page)| page.insert(cell_width, cell_height).map(|pos| (i, pos)));
+        if placed.is_none() {
+            let mut page = AtlasPage::new(self.page_width, self.page_height, self.params);
+            let pos = page.insert(cell_width, cell_height).unwrap();
+            self.pages.push(page);
+            placed = Some((self.pages.len() - 1, pos));
+        }
+        let (index, (x, y)) = placed.unwrap();
+        
+        let page = &mut self.pages[index];
+        page.texture.update_region(x, y, &extrude(img, self.extrude));
+        
+        AtlasRegion {
+            page: index,
+            region: TextureRegion::new(&page.texture, x + self.extrude, y + self.extrude, width, height),
+        }
+    }
+    
+    /// Packs several images at once, largest first for a tighter fit. Regions are returned in the order
+    /// of `imgs`.
+    pub fn add_all(&mut self, imgs: &[RgbaImage]) -> Vec<AtlasRegion> {
+        let mut order: Vec<usize> = (0..imgs.len()).collect();
+        order.sort_by_key(|i| std::cmp::Reverse((imgs[*i].height(), imgs[*i].width())));
+        
+        let mut regions = vec![None; imgs.len()];
+        for i in order {
+            regions[i] = Some(self.add(&imgs[i]));
+        }
+        
+        regions.into_iter().map(Option::unwrap).collect()
+    }
+    
+    pub fn page(&self, index: usize) -> &Texture {
+        &self.pages[index].texture
+    }
+    
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+    
+    /// Fraction of each page's area covered by images, padding included
+    pub fn occupancy(&self) -> Vec<f32> {
+        self.pages.iter().map(|page| page.used as f32 / (self.page_width * self.page_height) as f32).collect()
+    }
+}
+
+/// A horizontal segment of the skyline; everything above `y` (from the top of the page) is filled.
+#[derive(Debug, Clone, Copy)]
+struct Skyline {
+    x: u32,
+    y: u32,
+    width: u32,
+}
+
+struct AtlasPage {
+    texture: Texture,
+    skyline: Vec<Skyline>,
+    used: u64,
+}
+impl AtlasPage {
+    fn new(width: u32, height: u32, params: TextureParams) -> Self {
+        Self {
+            texture: Texture::from_image_opts(RgbaImage::new(width, height), params, false),
+            skyline: vec![Skyline { x: 0, y: 0, width }],
+            used: 0,
+        }
+    }
+    
+    /// Finds the position that keeps the skyline lowest and reserves it.
+    fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let mut best: Option<(usize, u32)> = None;
+        let mut best_bottom = u32::MAX;
+        let mut best_width = u32::MAX;
+        
+        for i in 0..self.skyline.len() {
+            if let Some(y) = self.fit(i, width, height) {
+                let node = self.skyline[i];
+                if y + height < best_bottom || (y + height == best_bottom && node.width < best_width) {
+                    best = Some((i, y));
+                    best_bottom = y + height;
+                    best_width = node.width;
+                }
+            }
+        }
+        
+        let (index, y) = best?;
+        let x = self.skyline[index].x;
+        self.add_level(index, x, y + height, width);
+        self.used += width as u64 * height as u64;
+        
+        Some((x, y))
+    }
+    
+    /// Top of a `width`x`height` rectangle placed at the start of skyline node `index`, if it fits.
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.skyline[index].x;
+        if x + width > self.texture.width {
+            return None;
+        }
+        
+        let mut y = 0;
+        let mut covered = 0;
+        for node in &self.skyline[index..] {
+            if covered >= width {
+                break;
+            }
+            y = y.max(node.y);
+            if y + height > self.texture.height {
+                return None;
+            }
+            covered += node.width;
+        }
+        
+        Some(y)
+    }
+    
+    fn add_level(&mut self, index: usize, x: u32, y: u32, width: u32) {
+        self.skyline.insert(index, Skyline { x, y, width });
+        
+        // shrink or remove the nodes now covered by the new one
+        let end = x + width;
+        let i = index + 1;
+        while i < self.skyline.len() && self.skyline[i].x < end {
+            let node = &mut self.skyline[i];
+            let overlap = end - node.x;
+            if node.width <= overlap {
+                self.skyline.remove(i);
+            } else {
+                node.x += overlap;
+                node.width -= overlap;
+                break;
+            }
+        }
+        
+        let mut i = 0;
+        while i + 1 < self.skyline.len() {
+            if self.skyline[i].y == self.skyline[i + 1].y {
+                self.skyline[i].width += self.skyline[i + 1].width;
+                self.skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Surrounds `img` with `amount` copies of its edge pixels.
+fn extrude(img: &RgbaImage, amount: u32) -> RgbaImage {
+    if amount == 0 {
+        return img.clone();
+    }
+    
+    let (width, height) = img.dimensions();
+    RgbaImage::from_fn(width + amount * 2, height + amount * 2, |x, y| {
+        let src_x = x.saturating_sub(amount).min(width - 1);
+        let src_y = y.saturating_sub(amount).min(height - 1);
+        
+        *img.get_pixel(src_x, src_y)
+    })
+}
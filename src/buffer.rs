@@ -0,0 +1,331 @@
+use std::marker::PhantomData;
+use std::ffi::c_void;
+use gl::types::*;
+
+/// Memory layout rules for interface blocks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    /// Used by uniform blocks. Array strides and struct alignments are rounded up to 16 bytes.
+    Std140,
+    /// Used by shader storage blocks. Same as std140, without the 16 byte rounding.
+    Std430,
+}
+
+/// GLSL type of a block member
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockType {
+    Float,
+    Int,
+    UInt,
+    Vec2,
+    Vec3,
+    Vec4,
+    IVec4,
+    Mat4,
+    /// A nested struct; its fields, and the size of the matching Rust type
+    Struct(&'static [BlockField], usize),
+}
+impl BlockType {
+    /// Nested struct member for `T`, to be used with arrays of lights, materials, etc.
+    pub const fn of<T: BufferBlock>() -> BlockType {
+        BlockType::Struct(T::FIELDS, std::mem::size_of::<T>())
+    }
+    
+    fn align(&self, layout: Layout) -> usize {
+        match *self {
+            BlockType::Float | BlockType::Int | BlockType::UInt => 4,
+            BlockType::Vec2 => 8,
+            BlockType::Vec3 | BlockType::Vec4 | BlockType::IVec4 | BlockType::Mat4 => 16,
+            BlockType::Struct(fields, _) => {
+                let align = fields.iter().map(|f| f.align(layout)).max().unwrap_or(4);
+                
+                round_layout(align, layout)
+            },
+        }
+    }
+    
+    fn size(&self, layout: Layout) -> usize {
+        match *self {
+            BlockType::Float | BlockType::Int | BlockType::UInt => 4,
+            BlockType::Vec2 => 8,
+            BlockType::Vec3 => 12,
+            BlockType::Vec4 | BlockType::IVec4 => 16,
+            BlockType::Mat4 => 64,
+            BlockType::Struct(fields, _) => {
+                let end = fields.iter().fold(0, |end, f| round_up(end, f.align(layout)) + f.size(layout));
+                
+                round_up(end, self.align(layout))
+            },
+        }
+    }
+    
+    /// Size of the Rust type expected to hold this member, e.g. `[f32; 3]` for `Vec3`
+    fn rust_size(&self) -> usize {
+        match *self {
+            BlockType::Struct(_, size) => size,
+            BlockType::Vec3 => 12,
+            _ => self.size(Layout::Std430),
+        }
+    }
+}
+
+/// A single member of a `BufferBlock`. Usually created with the `block_field!` macro.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockField {
+    pub name: &'static str,
+    pub ty: BlockType,
+    /// Array length, or 0 if the member isn't an array
+    pub count: usize,
+    /// Offset of the field in the Rust struct
+    pub offset: usize,
+}
+impl BlockField {
+    fn align(&self, layout: Layout) -> usize {
+        if self.count > 0 {
+            round_layout(self.ty.align(layout), layout)
+        } else {
+            self.ty.align(layout)
+        }
+    }
+    
+    fn stride(&self, layout: Layout) -> usize {
+        round_up(self.ty.size(layout), self.align(layout))
+    }
+    
+    fn size(&self, layout: Layout) -> usize {
+        if self.count > 0 {
+            self.stride(layout) * self.count
+        } else {
+            self.ty.size(layout)
+        }
+    }
+}
+
+/// A `#[repr(C)]` struct that can be uploaded to a uniform or shader storage block.
+///
+/// `FIELDS` must list every member of the GLSL block in declaration order. The Rust offsets are checked
+/// against the std140/std430 rules when a buffer is created, so padding mistakes panic early instead of
+/// producing garbage in the shader.
+///
+/// # Safety
+/// The type must be `#[repr(C)]`, and must not contain pointers or references, as its bytes are copied
+/// directly to the GPU.
+pub unsafe trait BufferBlock: Copy {
+    const FIELDS: &'static [BlockField];
+}
+
+/// Describes a field of a `BufferBlock`: `block_field!(Camera, view, BlockType::Mat4)`, or with an
+/// array length: `block_field!(Lights, points, BlockType::of::<PointLight>(), 16)`
+#[macro_export]
+macro_rules! block_field {
+    ($block:ty, $field:ident, $ty:expr) => {
+        $crate::buffer::BlockField { name: stringify!($field), ty: $ty, count: 0, offset: std::mem::offset_of!($block, $field) }
+    };
+    ($block:ty, $field:ident, $ty:expr, $count:expr) => {
+        $crate::buffer::BlockField { name: stringify!($field), ty: $ty, count: $count, offset: std::mem::offset_of!($block, $field) }
+    };
+}
+
+fn round_up(val: usize, align: usize) -> usize {
+    val.next_multiple_of(align)
+}
+
+fn round_layout(align: usize, layout: Layout) -> usize {
+    match layout {
+        Layout::Std140 => round_up(align, 16),
+        Layout::Std430 => align,
+    }
+}
+
+/// Panics with a description of the first member of `T` which doesn't match `layout`.
+pub fn check_layout<T: BufferBlock>(layout: Layout) {
+    check_fields(std::any::type_name::<T>(), T::FIELDS, std::mem::size_of::<T>(), layout);
+}
+
+fn check_fields(type_name: &str, fields: &[BlockField], rust_size: usize, layout: Layout) {
+    let mut cursor = 0;
+    for field in fields {
+        let expected = round_up(cursor, field.align(layout));
+        if field.offset != expected {
+            panic!("Field `{}` of {} is at offset {}, but {:?} expects offset {}", field.name, type_name, field.offset, layout, expected);
+        }
+        
+        if field.count > 0 && field.stride(layout) != field.ty.rust_size() {
+            panic!("Array `{}` of {} has an element size of {} bytes, but {:?} uses a stride of {}", field.name, type_name, field.ty.rust_size(), layout, field.stride(layout));
+        }
+        
+        if let BlockType::Struct(inner, size) = field.ty {
+            check_fields(field.name, inner, size, layout);
+        }
+        
+        cursor = expected + field.size(layout);
+    }
+    
+    if rust_size < cursor {
+        panic!("{} is {} bytes, but {:?} requires at least {}", type_name, rust_size, layout, cursor);
+    }
+}
+
+/// Uniform buffer object holding a single `T`, laid out as std140.
+///
+/// The buffer stays bound to `binding`; link it to a program with `ShaderProgram::bind_uniform_block`.
+pub struct UniformBuffer<T: BufferBlock> {
+    name: GLuint,
+    binding: u32,
+    _marker: PhantomData<T>,
+}
+impl<T: BufferBlock> UniformBuffer<T> {
+    pub fn new(binding: u32) -> Self {
+        check_layout::<T>(Layout::Std140);
+        check_binding(gl::MAX_UNIFORM_BUFFER_BINDINGS, binding);
+        
+        let mut name = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut name);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, name);
+            gl::BufferData(gl::UNIFORM_BUFFER, std::mem::size_of::<T>() as GLsizeiptr, std::ptr::null(), gl::DYNAMIC_DRAW);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, name);
+        }
+        
+        Self {
+            name,
+            binding,
+            _marker: PhantomData,
+        }
+    }
+    
+    pub fn set(&self, data: &T) {
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.name);
+            gl::BufferSubData(gl::UNIFORM_BUFFER, 0, std::mem::size_of::<T>() as GLsizeiptr, data as *const T as *const c_void);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+    }
+    
+    /// Rebinds the buffer to its binding point, in case something else was bound there since creation.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, self.binding, self.name);
+        }
+    }
+    
+    pub fn binding(&self) -> u32 {
+        self.binding
+    }
+}
+impl<T: BufferBlock> Drop for UniformBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.name);
+        }
+    }
+}
+
+/// Shader storage buffer object holding an array of `T`, laid out as std430.
+///
+/// Matches a GLSL block ending in a runtime-sized array, e.g. `buffer Lights { Light lights[]; };`.
+/// Link it to a program with `ShaderProgram::bind_storage_block`.
+pub struct StorageBuffer<T: BufferBlock> {
+    name: GLuint,
+    binding: u32,
+    capacity: usize,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+impl<T: BufferBlock> StorageBuffer<T> {
+    pub fn new(binding: u32, capacity: usize) -> Self {
+        check_layout::<T>(Layout::Std430);
+        check_binding(gl::MAX_SHADER_STORAGE_BUFFER_BINDINGS, binding);
+        
+        let mut name = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut name);
+        }
+        
+        let mut buf = Self {
+            name,
+            binding,
+            capacity: 0,
+            len: 0,
+            _marker: PhantomData,
+        };
+        buf.allocate(capacity.max(1));
+        
+        buf
+    }
+    
+    fn allocate(&mut self, capacity: usize) {
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.name);
+            gl::BufferData(gl::SHADER_STORAGE_BUFFER, (capacity * std::mem::size_of::<T>()) as GLsizeiptr, std::ptr::null(), gl::DYNAMIC_DRAW);
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, self.binding, self.name);
+        }
+        self.capacity = capacity;
+    }
+    
+    /// Replaces the contents of the buffer, growing it if `data` doesn't fit.
+    pub fn set(&mut self, data: &[T]) {
+        if data.len() > self.capacity {
+            self.allocate(data.len().next_power_of_two());
+        }
+        
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.name);
+            gl::BufferSubData(gl::SHADER_STORAGE_BUFFER, 0, std::mem::size_of_val(data) as GLsizeiptr, data.as_ptr() as *const c_void);
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+        self.len = data.len();
+    }
+    
+    /// Reads the contents back from the GPU, e.g. after a compute shader wrote to it.
+    pub fn read(&self) -> Vec<T> {
+        let mut data = Vec::with_capacity(self.len);
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.name);
+            gl::GetBufferSubData(gl::SHADER_STORAGE_BUFFER, 0, (self.len * std::mem::size_of::<T>()) as GLsizeiptr, data.as_mut_ptr() as *mut c_void);
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+            data.set_len(self.len);
+        }
+        
+        data
+    }
+    
+    /// Rebinds the buffer to its binding point, in case something else was bound there since creation.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, self.binding, self.name);
+        }
+    }
+    
+    pub fn binding(&self) -> u32 {
+        self.binding
+    }
+    
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+impl<T: BufferBlock> Drop for StorageBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.name);
+        }
+    }
+}
+
+fn check_binding(limit: GLenum, binding: u32) {
+    let mut max = 0;
+    unsafe {
+        gl::GetIntegerv(limit, &mut max);
+    }
+    
+    if binding as GLint >= max {
+        panic!("Buffer binding point {} exceeds the driver's limit of {}", binding, max);
+    }
+}
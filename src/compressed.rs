@@ -0,0 +1,575 @@
+use std::ffi::c_void;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use gl::types::*;
+use crate::graphics::{Texture, TextureFormat, TextureParams};
+
+// S3TC isn't core, so the bindings don't include it
+const COMPRESSED_RGB_S3TC_DXT1: GLenum = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT1: GLenum = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3: GLenum = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5: GLenum = 0x83F3;
+const COMPRESSED_SRGB_S3TC_DXT1: GLenum = 0x8C4C;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1: GLenum = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT3: GLenum = 0x8C4E;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5: GLenum = 0x8C4F;
+
+const KTX2_IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// Block compressed formats which can be loaded from KTX2 and DDS files.
+///
+/// BC1-BC5 (S3TC/RGTC) have a CPU decoder, used when the driver doesn't support the format. BC7 and ETC2
+/// are core since GL 4.3, so every context created by `Screen` supports them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressedFormat {
+    Bc1Rgb,
+    Bc1Rgba,
+    Bc1RgbSrgb,
+    Bc1RgbaSrgb,
+    Bc2,
+    Bc2Srgb,
+    Bc3,
+    Bc3Srgb,
+    Bc4,
+    Bc5,
+    Bc7,
+    Bc7Srgb,
+    Etc2Rgb8,
+    Etc2Srgb8,
+    Etc2Rgb8A1,
+    Etc2Srgb8A1,
+    Etc2Rgba8,
+    Etc2Srgb8Alpha8,
+}
+impl CompressedFormat {
+    pub fn internal_format(&self) -> GLenum {
+        match *self {
+            CompressedFormat::Bc1Rgb => COMPRESSED_RGB_S3TC_DXT1,
+            CompressedFormat::Bc1Rgba => COMPRESSED_RGBA_S3TC_DXT1,
+            CompressedFormat::Bc1RgbSrgb => COMPRESSED_SRGB_S3TC_DXT1,
+            CompressedFormat::Bc1RgbaSrgb => COMPRESSED_SRGB_ALPHA_S3TC_DXT1,
+            CompressedFormat::Bc2 => COMPRESSED_RGBA_S3TC_DXT3,
+            CompressedFormat::Bc2Srgb => COMPRESSED_SRGB_ALPHA_S3TC_DXT3,
+            CompressedFormat::Bc3 => COMPRESSED_RGBA_S3TC_DXT5,
+            CompressedFormat::Bc3Srgb => COMPRESSED_SRGB_ALPHA_S3TC_DXT5,
+            CompressedFormat::Bc4 => gl::COMPRESSED_RED_RGTC1,
+            CompressedFormat::Bc5 => gl::COMPRESSED_RG_RGTC2,
+            CompressedFormat::Bc7 => gl::COMPRESSED_RGBA_BPTC_UNORM,
+            CompressedFormat::Bc7Srgb => gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
+            CompressedFormat::Etc2Rgb8 => gl::COMPRESSED_RGB8_ETC2,
+            CompressedFormat::Etc2Srgb8 => gl::COMPRESSED_SRGB8_ETC2,
+            CompressedFormat::Etc2Rgb8A1 => gl::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+            CompressedFormat::Etc2Srgb8A1 => gl::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+            CompressedFormat::Etc2Rgba8 => gl::COMPRESSED_RGBA8_ETC2_EAC,
+            CompressedFormat::Etc2Srgb8Alpha8 => gl::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,
+        }
+    }
+    
+    /// Size of one 4x4 block, in bytes
+    pub fn block_size(&self) -> usize {
+        match *self {
+            CompressedFormat::Bc1Rgb | CompressedFormat::Bc1Rgba | CompressedFormat::Bc1RgbSrgb | CompressedFormat::Bc1RgbaSrgb
+                | CompressedFormat::Bc4 | CompressedFormat::Etc2Rgb8 | CompressedFormat::Etc2Srgb8
+                | CompressedFormat::Etc2Rgb8A1 | CompressedFormat::Etc2Srgb8A1 => 8,
+            _ => 16,
+        }
+    }
+    
+    /// Size of a `width`x`height` image in this format, in bytes
+    pub fn level_size(&self, width: u32, height: u32) -> usize {
+        width.div_ceil(4) as usize * height.div_ceil(4) as usize * self.block_size()
+    }
+    
+    /// Whether the driver can sample this format directly.
+    pub fn is_supported(&self) -> bool {
+        let mut supported = 0;
+        unsafe {
+            gl::GetInternalformativ(gl::TEXTURE_2D, self.internal_format(), gl::INTERNALFORMAT_SUPPORTED, 1, &mut supported);
+        }
+        
+        supported == gl::TRUE as GLint
+    }
+    
+    fn has_cpu_decoder(&self) -> bool {
+        matches!(*self, CompressedFormat::Bc1Rgb | CompressedFormat::Bc1Rgba | CompressedFormat::Bc1RgbSrgb | CompressedFormat::Bc1RgbaSrgb
+            | CompressedFormat::Bc2 | CompressedFormat::Bc2Srgb | CompressedFormat::Bc3 | CompressedFormat::Bc3Srgb
+            | CompressedFormat::Bc4 | CompressedFormat::Bc5)
+    }
+    
+    fn is_srgb(&self) -> bool {
+        matches!(*self, CompressedFormat::Bc1RgbSrgb | CompressedFormat::Bc1RgbaSrgb | CompressedFormat::Bc2Srgb | CompressedFormat::Bc3Srgb
+            | CompressedFormat::Bc7Srgb | CompressedFormat::Etc2Srgb8 | CompressedFormat::Etc2Srgb8A1 | CompressedFormat::Etc2Srgb8Alpha8)
+    }
+    
+    fn from_vk_format(format: u32) -> Option<Self> {
+        Some(match format {
+            131 => CompressedFormat::Bc1Rgb,
+            132 => CompressedFormat::Bc1RgbSrgb,
+            133 => CompressedFormat::Bc1Rgba,
+            134 => CompressedFormat::Bc1RgbaSrgb,
+            135 => CompressedFormat::Bc2,
+            136 => CompressedFormat::Bc2Srgb,
+            137 => CompressedFormat::Bc3,
+            138 => CompressedFormat::Bc3Srgb,
+            139 => CompressedFormat::Bc4,
+            141 => CompressedFormat::Bc5,
+            145 => CompressedFormat::Bc7,
+            146 => CompressedFormat::Bc7Srgb,
+            147 => CompressedFormat::Etc2Rgb8,
+            148 => CompressedFormat::Etc2Srgb8,
+            149 => CompressedFormat::Etc2Rgb8A1,
+            150 => CompressedFormat::Etc2Srgb8A1,
+            151 => CompressedFormat::Etc2Rgba8,
+            152 => CompressedFormat::Etc2Srgb8Alpha8,
+            _ => return None,
+        })
+    }
+    
+    fn from_dxgi_format(format: u32) -> Option<Self> {
+        Some(match format {
+            71 => CompressedFormat::Bc1Rgba,
+            72 => CompressedFormat::Bc1RgbaSrgb,
+            74 => CompressedFormat::Bc2,
+            75 => CompressedFormat::Bc2Srgb,
+            77 => CompressedFormat::Bc3,
+            78 => CompressedFormat::Bc3Srgb,
+            80 => CompressedFormat::Bc4,
+            83 => CompressedFormat::Bc5,
+            98 => CompressedFormat::Bc7,
+            99 => CompressedFormat::Bc7Srgb,
+            _ => return None,
+        })
+    }
+    
+    fn from_four_cc(four_cc: &[u8]) -> Option<Self> {
+        Some(match four_cc {
+            b"DXT1" => CompressedFormat::Bc1Rgba,
+            b"DXT2" | b"DXT3" => CompressedFormat::Bc2,
+            b"DXT4" | b"DXT5" => CompressedFormat::Bc3,
+            b"ATI1" | b"BC4U" => CompressedFormat::Bc4,
+            b"ATI2" | b"BC5U" => CompressedFormat::Bc5,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum CompressedError {
+    Io(std::io::Error),
+    InvalidHeader(&'static str),
+    /// The file's pixel format isn't one of `CompressedFormat`
+    UnsupportedFormat(String),
+    /// KTX2 supercompression (BasisLU, Zstandard, ZLIB) isn't supported
+    Supercompressed(u32),
+    /// The driver doesn't support the format, and there's no CPU decoder for it
+    NoFallback(CompressedFormat),
+}
+impl Display for CompressedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompressedError::Io(err) => write!(f, "Error reading compressed texture: {}", err),
+            CompressedError::InvalidHeader(msg) => write!(f, "Invalid compressed texture header: {}", msg),
+            CompressedError::UnsupportedFormat(format) => write!(f, "Unsupported compressed texture format: {}", format),
+            CompressedError::Supercompressed(scheme) => write!(f, "Unsupported KTX2 supercompression scheme {}", scheme),
+            CompressedError::NoFallback(format) => write!(f, "Driver doesn't support {:?}, and it has no CPU decoder", format),
+        }
+    }
+}
+impl std::error::Error for CompressedError {}
+impl From<std::io::Error> for CompressedError {
+    fn from(err: std::io::Error) -> Self {
+        CompressedError::Io(err)
+    }
+}
+
+/// A parsed compressed image: its format, and the data of each mip level starting with the full size image.
+#[derive(Debug, Clone)]
+pub struct CompressedImage {
+    pub format: CompressedFormat,
+    pub width: u32,
+    pub height: u32,
+    pub levels: Vec<Vec<u8>>,
+}
+impl CompressedImage {
+    /// Parses a KTX2 or DDS file, chosen by its contents rather than its extension.
+    pub fn from_path(path: &Path) -> Result<Self, CompressedError> {
+        let data = std::fs::read(path)?;
+        if data.starts_with(&KTX2_IDENTIFIER) {
+            Self::from_ktx2(&data)
+        } else {
+            Self::from_dds(&data)
+        }
+    }
+    
+    /// Parses the first layer/face of a KTX2 file. Only non-supercompressed files are supported.
+    pub fn from_ktx2(data: &[u8]) -> Result<Self, CompressedError> {
+        if !data.starts_with(&KTX2_IDENTIFIER) || data.len() < 80 {
+            return Err(CompressedError::InvalidHeader("not a KTX2 file"));
+        }
+        
+        let vk_format = read_u32(data, 12);
+        let width = read_u32(data, 20);
+        let height = read_u32(data, 24).max(1);
+        let level_count = read_u32(data, 40).max(1);
+        let supercompression = read_u32(data, 44);
+        
+        if supercompression != 0 {
+            return Err(CompressedError::Supercompressed(supercompression));
+        }
+        let format = CompressedFormat::from_vk_format(vk_format).ok_or_else(|| CompressedError::UnsupportedFormat(format!("VkFormat {}", vk_format)))?;
+        check_size(width, height, level_count)?;
+        
+        let mut levels = Vec::with_capacity(level_count as usize);
+        for level in 0..level_count {
+            let index = 80 + level as usize * 24;
+            if data.len() < index + 24 {
+                return Err(CompressedError::InvalidHeader("truncated level index"));
+            }
+            
+            // the first layer/face of each level comes first in its data
+            let len = format.level_size(mip_size(width, level), mip_size(height, level));
+            levels.push(level_data(data, read_u64(data, index), len)?.to_vec());
+        }
+        
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+    
+    /// Parses the first surface of a DDS file, including DX10 extended headers.
+    pub fn from_dds(data: &[u8]) -> Result<Self, CompressedError> {
+        if data.len() < 128 || &data[0..4] != b"DDS " || read_u32(data, 4) != 124 {
+            return Err(CompressedError::InvalidHeader("not a DDS file"));
+        }
+        
+        let height = read_u32(data, 12);
+        let width = read_u32(data, 16);
+        let level_count = read_u32(data, 28).max(1);
+        let four_cc = &data[84..88];
+        
+        let (format, mut offset) = if four_cc == b"DX10" {
+            if data.len() < 148 {
+                return Err(CompressedError::InvalidHeader("truncated DX10 header"));
+            }
+            let dxgi = read_u32(data, 128);
+            let format = CompressedFormat::from_dxgi_format(dxgi).ok_or_else(|| CompressedError::UnsupportedFormat(format!("DXGI format {}", dxgi)))?;
+            
+            (format, 148)
+        } else {
+            let format = CompressedFormat::from_four_cc(four_cc).ok_or_else(|| CompressedError::UnsupportedFormat(format!("FourCC {:?}", String::from_utf8_lossy(four_cc))))?;
+            
+            (format, 128)
+        };
+        check_size(width, height, level_count)?;
+        
+        let mut levels = Vec::with_capacity(level_count as usize);
+        for level in 0..level_count {
+            let len = format.level_size(mip_size(width, level), mip_size(height, level));
+            levels.push(level_data(data, offset as u64, len)?.to_vec());
+            offset += len;
+        }
+        
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+    
+    /// Decodes a mip level to RGBA8, if this format has a CPU decoder.
+    pub fn decode_level(&self, level: usize) -> Option<Vec<u8>> {
+        if !self.format.has_cpu_decoder() {
+            return None;
+        }
+        
+        let width = mip_size(self.width, level as u32);
+        let height = mip_size(self.height, level as u32);
+        
+        Some(decode(self.format, &self.levels[level], width, height))
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    read_u32(data, offset) as u64 | (read_u32(data, offset + 4) as u64) << 32
+}
+
+fn mip_size(size: u32, level: u32) -> u32 {
+    size.checked_shr(level).unwrap_or(0).max(1)
+}
+
+/// Largest width or height accepted from a file header, well above what drivers support
+const MAX_SIZE: u32 = 1 << 16;
+
+/// Rejects sizes and mip counts no valid file has, before anything is allocated for them
+fn check_size(width: u32, height: u32, level_count: u32) -> Result<(), CompressedError> {
+    if width == 0 || height == 0 || width > MAX_SIZE || height > MAX_SIZE {
+        return Err(CompressedError::InvalidHeader("invalid image size"));
+    }
+    if level_count > 32 - width.max(height).leading_zeros() {
+        return Err(CompressedError::InvalidHeader("more mip levels than the image size allows"));
+    }
+    
+    Ok(())
+}
+
+fn level_data(data: &[u8], offset: u64, len: usize) -> Result<&[u8], CompressedError> {
+    usize::try_from(offset).ok()
+        .and_then(|offset| Some(offset..offset.checked_add(len)?))
+        .and_then(|range| data.get(range))
+        .ok_or(CompressedError::InvalidHeader("level data out of bounds"))
+}
+
+impl Texture {
+    /// Loads a KTX2 or DDS file, keeping its mip chain.
+    ///
+    /// The data is uploaded as-is when the driver supports the format, and decoded to RGBA8 on the CPU
+    /// otherwise. Compressed blocks can't be flipped, so unlike `from_image` the first row of the file is
+    /// at v = 0.
+    pub fn from_compressed_path(path: &Path, params: TextureParams) -> Result<Self, CompressedError> {
+        Self::from_compressed(&CompressedImage::from_path(path)?, params)
+    }
+    
+    pub fn from_compressed(img: &CompressedImage, params: TextureParams) -> Result<Self, CompressedError> {
+        let native = img.format.is_supported();
+        if !native && !img.format.has_cpu_decoder() {
+            return Err(CompressedError::NoFallback(img.format));
+        }
+        
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            params.apply(gl::TEXTURE_2D);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, img.levels.len() as GLint - 1);
+            
+            for (level, data) in img.levels.iter().enumerate() {
+                let width = mip_size(img.width, level as u32) as GLsizei;
+                let height = mip_size(img.height, level as u32) as GLsizei;
+                
+                if native {
+                    gl::CompressedTexImage2D(gl::TEXTURE_2D, level as GLint, img.format.internal_format(), width, height, 0, data.len() as GLsizei, data.as_ptr() as *const c_void);
+                } else {
+                    let internal = if img.format.is_srgb() { gl::SRGB8_ALPHA8 } else { gl::RGBA8 };
+                    let rgba = img.decode_level(level).unwrap();
+                    gl::TexImage2D(gl::TEXTURE_2D, level as GLint, internal as GLint, width, height, 0, gl::RGBA, gl::UNSIGNED_BYTE, rgba.as_ptr() as *const c_void);
+                }
+            }
+            
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        
+        let format = if img.format.is_srgb() { TextureFormat::Srgb8Alpha8 } else { TextureFormat::Rgba8 };
+        
+        Ok(Texture::from_raw(id, img.width, img.height, format, params, native.then_some(img.format)))
+    }
+}
+
+/// Decodes BC1-BC5 data to RGBA8
+fn decode(format: CompressedFormat, data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let blocks_x = width.div_ceil(4);
+    let block_size = format.block_size();
+    
+    let mut out = vec![0u8; width * height * 4];
+    for (i, block) in data.chunks_exact(block_size).enumerate() {
+        let (bx, by) = (i % blocks_x * 4, i / blocks_x * 4);
+        
+        let mut texels = [[0u8, 0, 0, 255]; 16];
+        match format {
+            CompressedFormat::Bc1Rgb | CompressedFormat::Bc1RgbSrgb => {
+                decode_color(&block[0..8], &mut texels, true);
+                texels.iter_mut().for_each(|texel| texel[3] = 255);
+            },
+            CompressedFormat::Bc1Rgba | CompressedFormat::Bc1RgbaSrgb => decode_color(&block[0..8], &mut texels, true),
+            CompressedFormat::Bc2 | CompressedFormat::Bc2Srgb => {
+                decode_color(&block[8..16], &mut texels, false);
+                for (j, texel) in texels.iter_mut().enumerate() {
+                    let alpha = (block[j / 2] >> (j % 2 * 4)) & 0xF;
+                    texel[3] = alpha * 17;
+                }
+            },
+            CompressedFormat::Bc3 | CompressedFormat::Bc3Srgb => {
+                decode_color(&block[8..16], &mut texels, false);
+                decode_channel(&block[0..8], &mut texels, 3);
+            },
+            CompressedFormat::Bc4 => decode_channel(&block[0..8], &mut texels, 0),
+            CompressedFormat::Bc5 => {
+                decode_channel(&block[0..8], &mut texels, 0);
+                decode_channel(&block[8..16], &mut texels, 1);
+            },
+            _ => unreachable!(),
+        }
+        
+        for (j, texel) in texels.iter().enumerate() {
+            let (x, y) = (bx + j % 4, by + j / 4);
+            if x < width && y < height {
+                let idx = (y * width + x) * 4;
+                out[idx..idx + 4].copy_from_slice(texel);
+            }
+        }
+    }
+    
+    out
+}
+
+/// Decodes a BC1 color block. BC2/BC3 color blocks always use the four color mode and keep the texels'
+/// alpha, so `punch_through` enables the three color + transparent mode only for BC1.
+fn decode_color(block: &[u8], texels: &mut [[u8; 4]; 16], punch_through: bool) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = read_u32(block, 4);
+    
+    let expand = |c: u16| {
+        let r = (c >> 11 & 0x1F) as u32;
+        let g = (c >> 5 & 0x3F) as u32;
+        let b = (c & 0x1F) as u32;
+        
+        [(r << 3 | r >> 2), (g << 2 | g >> 4), (b << 3 | b >> 2)]
+    };
+    let (a, b) = (expand(c0), expand(c1));
+    let mix = |wa: u32, wb: u32, div: u32| [0, 1, 2].map(|i| ((a[i] * wa + b[i] * wb) / div) as u8);
+    
+    let palette: [[u8; 4]; 4] = if c0 > c1 || !punch_through {
+        let (c2, c3) = (mix(2, 1, 3), mix(1, 2, 3));
+        [[a[0] as u8, a[1] as u8, a[2] as u8, 255], [b[0] as u8, b[1] as u8, b[2] as u8, 255], [c2[0], c2[1], c2[2], 255], [c3[0], c3[1], c3[2], 255]]
+    } else {
+        let c2 = mix(1, 1, 2);
+        [[a[0] as u8, a[1] as u8, a[2] as u8, 255], [b[0] as u8, b[1] as u8, b[2] as u8, 255], [c2[0], c2[1], c2[2], 255], [0, 0, 0, 0]]
+    };
+    
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let alpha = texel[3];
+        *texel = palette[(indices >> (i * 2) & 0x3) as usize];
+        if !punch_through {
+            texel[3] = alpha;
+        }
+    }
+}
+
+/// Decodes a BC3 alpha / BC4 block into `channel` of each texel
+fn decode_channel(block: &[u8], texels: &mut [[u8; 4]; 16], channel: usize) {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let indices = block[2..8].iter().rev().fold(0u64, |bits, byte| bits << 8 | *byte as u64);
+    
+    let mut palette = [0u8; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * a0 + i as u32 * a1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * a0 + i as u32 * a1) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+    
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[channel] = palette[(indices >> (i * 3) & 0x7) as usize];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// 4x4 BC1 block: red and blue endpoints, texels 0-3 using palette entries 0-3
+    const BC1_BLOCK: [u8; 8] = [0x00, 0xF8, 0x1F, 0x00, 0xE4, 0x00, 0x00, 0x00];
+    
+    fn dds(width: u32, height: u32, level_count: u32, four_cc: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut file = vec![0u8; 128];
+        file[0..4].copy_from_slice(b"DDS ");
+        file[4..8].copy_from_slice(&124u32.to_le_bytes());
+        file[12..16].copy_from_slice(&height.to_le_bytes());
+        file[16..20].copy_from_slice(&width.to_le_bytes());
+        file[28..32].copy_from_slice(&level_count.to_le_bytes());
+        file[84..88].copy_from_slice(four_cc);
+        file.extend_from_slice(data);
+        
+        file
+    }
+    
+    fn ktx2(vk_format: u32, width: u32, height: u32, level_count: u32, offset: u64, data: &[u8]) -> Vec<u8> {
+        let mut file = vec![0u8; 104];
+        file[0..12].copy_from_slice(&KTX2_IDENTIFIER);
+        file[12..16].copy_from_slice(&vk_format.to_le_bytes());
+        file[20..24].copy_from_slice(&width.to_le_bytes());
+        file[24..28].copy_from_slice(&height.to_le_bytes());
+        file[40..44].copy_from_slice(&level_count.to_le_bytes());
+        file[80..88].copy_from_slice(&offset.to_le_bytes());
+        file[88..96].copy_from_slice(&(data.len() as u64).to_le_bytes());
+        file.extend_from_slice(data);
+        
+        file
+    }
+    
+    #[test]
+    fn decodes_bc1_block() {
+        let rgba = decode(CompressedFormat::Bc1Rgb, &BC1_BLOCK, 4, 4);
+        
+        assert_eq!(&rgba[0..16], &[255, 0, 0, 255, 0, 0, 255, 255, 170, 0, 85, 255, 85, 0, 170, 255]);
+        // the remaining texels use index 0
+        assert_eq!(&rgba[60..64], &[255, 0, 0, 255]);
+    }
+    
+    #[test]
+    fn decodes_bc4_block() {
+        // endpoints 255 and 0, texels 0-3 using palette entries 0, 1, 2 and 7
+        let block = [255, 0, 0x88, 0x0E, 0, 0, 0, 0];
+        let rgba = decode(CompressedFormat::Bc4, &block, 4, 4);
+        
+        let red: Vec<u8> = rgba.chunks_exact(4).take(4).map(|texel| texel[0]).collect();
+        assert_eq!(red, [255, 0, 218, 36]);
+    }
+    
+    #[test]
+    fn parses_dds() {
+        let img = CompressedImage::from_dds(&dds(4, 4, 1, b"DXT1", &BC1_BLOCK)).unwrap();
+        
+        assert_eq!(img.format, CompressedFormat::Bc1Rgba);
+        assert_eq!((img.width, img.height), (4, 4));
+        assert_eq!(img.levels, [BC1_BLOCK.to_vec()]);
+    }
+    
+    #[test]
+    fn parses_ktx2() {
+        let img = CompressedImage::from_ktx2(&ktx2(131, 4, 4, 1, 104, &BC1_BLOCK)).unwrap();
+        
+        assert_eq!(img.format, CompressedFormat::Bc1Rgb);
+        assert_eq!((img.width, img.height), (4, 4));
+        assert_eq!(img.levels, [BC1_BLOCK.to_vec()]);
+    }
+    
+    #[test]
+    fn rejects_truncated_headers() {
+        let file = dds(4, 4, 1, b"DXT1", &BC1_BLOCK);
+        assert!(CompressedImage::from_dds(&file[..100]).is_err());
+        assert!(CompressedImage::from_dds(&file[..130]).is_err());
+        
+        let file = ktx2(131, 4, 4, 1, 104, &BC1_BLOCK);
+        assert!(CompressedImage::from_ktx2(&file[..60]).is_err());
+        assert!(CompressedImage::from_ktx2(&file[..90]).is_err());
+    }
+    
+    #[test]
+    fn rejects_oversized_headers() {
+        assert!(CompressedImage::from_dds(&dds(1, 1, 33, b"DXT1", &BC1_BLOCK)).is_err());
+        assert!(CompressedImage::from_dds(&dds(4, 4, 4, b"DXT1", &[0; 64])).is_err());
+        assert!(CompressedImage::from_dds(&dds(u32::MAX, u32::MAX, 1, b"DXT1", &BC1_BLOCK)).is_err());
+        
+        assert!(CompressedImage::from_ktx2(&ktx2(131, 4, 4, u32::MAX, 104, &BC1_BLOCK)).is_err());
+        assert!(CompressedImage::from_ktx2(&ktx2(131, 4, 4, 1, u64::MAX, &BC1_BLOCK)).is_err());
+        assert!(CompressedImage::from_ktx2(&ktx2(131, 4, 4, 1, u64::MAX - 4, &BC1_BLOCK)).is_err());
+    }
+}
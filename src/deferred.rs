@@ -0,0 +1,523 @@
+use std::rc::Rc;
+use cgmath::{vec3, EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3};
+use gl::types::*;
+use crate::camera::Camera;
+use crate::framebuffer::{Attachment, Framebuffer, FramebufferError, Renderbuffer};
+use crate::graphics::{Mesh, ShaderProgram, Texture, TextureFormat, TextureParams, VertexAttributes, Wrap};
+use crate::lighting::{define_attributes, Light, SpotLight};
+use crate::material::{Material, MaterialInstance, UniformValue};
+use crate::shaders;
+
+/// Light volumes are polygons inside the shapes they approximate, so they're scaled up to cover them
+const VOLUME_SCALE: f32 = 1.1;
+
+/// A G-buffer channel shown by `DeferredRenderer::draw_debug`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GBufferView {
+    Albedo,
+    /// World space normals, mapped to 0-1
+    Normal,
+    Metallic,
+    Roughness,
+    Occlusion,
+    /// Depth, linearized between the camera's near and far planes
+    Depth,
+}
+
+/// The render targets of the geometry pass:
+/// 0. albedo (RGB) and ambient occlusion (A), `Rgba8`
+/// 1. world space normal (RGB), `Rgba16F`
+/// 2. metallic (R) and roughness (G), `Rgba8`
+///
+/// and a `Depth32F` depth texture. Every attachment is sampled with nearest filtering.
+pub struct GBuffer {
+    framebuffer: Framebuffer,
+}
+impl GBuffer {
+    pub fn new(width: u32, height: u32) -> Result<Self, FramebufferError> {
+        let params = TextureParams::default().with_wrap(Wrap::ClampToEdge);
+        
+        let mut framebuffer = Framebuffer::new(width, height);
+        for format in [TextureFormat::Rgba8, TextureFormat::Rgba16F, TextureFormat::Rgba8] {
+            framebuffer.attach_color(Attachment::Texture(Texture::empty(width, height, format, params)))?;
+        }
+        framebuffer.attach_depth(Attachment::Texture(Texture::empty(width, height, TextureFormat::Depth32F, params)))?;
+        framebuffer.check()?;
+        
+        Ok(Self { framebuffer })
+    }
+    
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        self.framebuffer.resize(width, height)
+    }
+    
+    pub fn albedo(&self) -> &Texture {
+        self.framebuffer.color(0)
+    }
+    
+    pub fn normals(&self) -> &Texture {
+        self.framebuffer.color(1)
+    }
+    
+    pub fn material(&self) -> &Texture {
+        self.framebuffer.color(2)
+    }
+    
+    pub fn depth(&self) -> &Texture {
+        self.framebuffer.depth().unwrap()
+    }
+    
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+    
+    /// Binds the attachments to units `first_unit` to `first_unit + 3` and sets the `gAlbedo`, `gNormal`,
+    /// `gMaterial` and `gDepth` samplers of `shader`, which must be bound.
+    pub fn bind_textures(&self, shader: &ShaderProgram, first_unit: u32) {
+        let textures = [("gAlbedo", self.albedo()), ("gNormal", self.normals()), ("gMaterial", self.material()), ("gDepth", self.depth())];
+        for (i, (sampler, texture)) in textures.into_iter().enumerate() {
+            texture.bind_to(first_unit + i as u32);
+            shader.set_uniform1i32(sampler, (first_unit + i as u32) as i32);
+        }
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+}
+
+/// Creates a geometry pass material for meshes with `attribs`, with the map features in `maps`, e.g.
+/// `&["ALBEDO_MAP", "NORMAL_MAP"]` (see `shaders::GBUFFER_FRAG`). The maps are set with
+/// `Material::set_texture`, using the feature name in camel case: `albedoMap`, `normalMap`,
+/// `metallicRoughnessMap` and `occlusionMap`.
+///
+/// Starts out white, non-metallic, with a roughness of 0.5 and no alpha cutoff.
+pub fn geometry_material(attribs: &VertexAttributes, maps: &[&str]) -> Material {
+    let mut shader = ShaderProgram::new();
+    define_attributes(&mut shader, attribs);
+    for map in maps {
+        shader.define(map, "1");
+    }
+    shader.create_vertex_shader(shaders::LIT_VERT);
+    shader.include(shaders::LIGHTING_GLSL);
+    shader.create_fragment_shader(shaders::GBUFFER_FRAG);
+    shader.link();
+    
+    Material::new(Rc::new(shader))
+        .with_uniform("albedo", UniformValue::Vec4([1.0, 1.0, 1.0, 1.0]))
+        .with_uniform("metallic", 0.0)
+        .with_uniform("roughness", 0.5)
+        .with_uniform("alphaCutoff", 0.0)
+}
+
+/// Deferred shading, for scenes with many lights.
+///
+/// The geometry pass renders the surfaces of the scene into a `GBuffer`; the lighting pass then shades each
+/// pixel once per light that reaches it, adding the results into an HDR output. Point and spot lights are
+/// drawn as spheres and cones, so only the pixels inside a light's volume are shaded for it.
+///
+/// ```ignore
+/// deferred.begin(&camera);
+/// deferred.draw(&mut mesh, &material, model);
+/// deferred.end();
+/// deferred.light(&camera, &lights);
+/// // e.g. draw `deferred.output().color(0)` through a PostProcessor with tone mapping
+/// ```
+///
+/// Geometry materials write the G-buffer layout, see `geometry_material`. Transparent objects can't be
+/// deferred: draw them into `output()` afterwards, which has the scene's depth.
+pub struct DeferredRenderer {
+    /// Ambient light color, applied to every surface with its occlusion
+    pub ambient: Vector3<f32>,
+    gbuffer: GBuffer,
+    output: Framebuffer,
+    ambient_shader: ShaderProgram,
+    directional_shader: ShaderProgram,
+    point_shader: ShaderProgram,
+    spot_shader: ShaderProgram,
+    debug_shader: ShaderProgram,
+    sphere: Mesh,
+    cone: Mesh,
+    vao: GLuint,
+    view_projection: Matrix4<f32>,
+}
+impl DeferredRenderer {
+    pub fn new(width: u32, height: u32) -> Result<Self, FramebufferError> {
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        
+        Ok(Self {
+            ambient: vec3(0.03, 0.03, 0.03),
+            gbuffer: GBuffer::new(width, height)?,
+            output: Self::output_target(width, height)?,
+            ambient_shader: light_shader("AMBIENT", shaders::FULLSCREEN_VERT),
+            directional_shader: light_shader("DIRECTIONAL", shaders::FULLSCREEN_VERT),
+            point_shader: light_shader("POINT", shaders::DEFERRED_VOLUME_VERT),
+            spot_shader: light_shader("SPOT", shaders::DEFERRED_VOLUME_VERT),
+            debug_shader: {
+                let mut shader = ShaderProgram::new();
+                shader.create_vertex_shader(shaders::FULLSCREEN_VERT);
+                shader.create_fragment_shader(shaders::GBUFFER_DEBUG_FRAG);
+                shader.link();
+                
+                shader
+            },
+            sphere: sphere_mesh(12, 8),
+            cone: cone_mesh(16),
+            vao,
+            view_projection: Matrix4::identity(),
+        })
+    }
+    
+    /// HDR color, and a depth buffer the G-buffer depth is copied into before lighting
+    fn output_target(width: u32, height: u32) -> Result<Framebuffer, FramebufferError> {
+        let mut output = Framebuffer::new(width, height);
+        output.attach_color(Attachment::Texture(Texture::empty(width, height, TextureFormat::Rgba16F, TextureParams::linear().with_wrap(Wrap::ClampToEdge))))?;
+        output.attach_depth(Attachment::Renderbuffer(Renderbuffer::new(width, height, TextureFormat::Depth32F)))?;
+        output.check()?;
+        
+        Ok(output)
+    }
+    
+    pub fn with_ambient(mut self, r: f32, g: f32, b: f32) -> Self {
+        self.ambient = vec3(r, g, b);
+        
+        self
+    }
+    
+    /// Recreates the G-buffer and output at the new size, e.g. when the window is resized.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        self.gbuffer.resize(width, height)?;
+        self.output.resize(width, height)
+    }
+    
+    /// Starts the geometry pass: binds and clears the G-buffer. Blending must be disabled while drawing.
+    pub fn begin(&mut self, camera: &Camera) {
+        self.view_projection = camera.combined;
+        self.gbuffer.framebuffer.bind();
+        self.gbuffer.framebuffer.clear(0.0, 0.0, 0.0, 0.0);
+    }
+    
+    /// Draws `mesh` with a geometry pass material, transformed by `model`.
+    pub fn draw(&self, mesh: &mut Mesh, material: &Material, model: Matrix4<f32>) {
+        material.apply();
+        material.shader.set_uniform_mat4f("model", model);
+        mesh.render(&material.shader, true, gl::TRIANGLES, self.view_projection * model);
+        material.shader.unbind();
+    }
+    
+    pub fn draw_instance(&self, mesh: &mut Mesh, instance: &MaterialInstance, model: Matrix4<f32>) {
+        instance.apply();
+        instance.material.shader.set_uniform_mat4f("model", model);
+        mesh.render(&instance.material.shader, true, gl::TRIANGLES, self.view_projection * model);
+        instance.material.shader.unbind();
+    }
+    
+    /// Ends the geometry pass, restoring the framebuffer bound before `begin`.
+    pub fn end(&self) {
+        self.gbuffer.framebuffer.unbind();
+    }
+    
+    /// Runs the lighting pass, replacing the contents of `output()`. `camera` must be the one passed to `begin`.
+    pub fn light(&mut self, camera: &Camera, lights: &[Light]) {
+        self.light_pass(camera, lights, None);
+    }
+    
+    /// Same as `light`, with the ambient light also darkened by `occlusion` (R, 1 for unoccluded), e.g.
+    /// `Ssao::occlusion`.
+    pub fn light_with_occlusion(&mut self, camera: &Camera, lights: &[Light], occlusion: &Texture) {
+        self.light_pass(camera, lights, Some(occlusion));
+    }
+    
+    fn light_pass(&mut self, camera: &Camera, lights: &[Light], occlusion: Option<&Texture>) {
+        let (width, height) = (self.output.width as GLint, self.output.height as GLint);
+        self.output.clear(0.0, 0.0, 0.0, 0.0);
+        unsafe {
+            gl::BlitNamedFramebuffer(self.gbuffer.framebuffer.id(), self.output.id(), 0, 0, width, height, 0, 0, width, height, gl::DEPTH_BUFFER_BIT, gl::NEAREST);
+        }
+        
+        let state = LightingState::save();
+        self.output.bind();
+        unsafe {
+            gl::DepthMask(gl::FALSE);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::ONE, gl::ONE);
+            gl::BlendEquation(gl::FUNC_ADD);
+        }
+        
+        let inverse = camera.combined.invert().unwrap_or(Matrix4::identity());
+        let setup = |shader: &ShaderProgram| {
+            shader.bind();
+            self.gbuffer.bind_textures(shader, 0);
+            shader.set_uniform_mat4f("invViewProj", inverse);
+            shader.set_uniform3f32("cameraPosition", camera.position.x, camera.position.y, camera.position.z);
+            shader.set_uniform2f32("screenSize", width as f32, height as f32);
+        };
+        
+        // fullscreen passes
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::CULL_FACE);
+        }
+        setup(&self.ambient_shader);
+        self.ambient_shader.set_uniform3f32("ambient", self.ambient.x, self.ambient.y, self.ambient.z);
+        self.ambient_shader.set_uniform1i32("occlusionMap", 4);
+        self.ambient_shader.set_uniform1i32("useOcclusionMap", occlusion.is_some() as i32);
+        if let Some(occlusion) = occlusion {
+            occlusion.bind_to(4);
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0);
+            }
+        }
+        self.fullscreen();
+        
+        setup(&self.directional_shader);
+        for light in lights {
+            if let Light::Directional(light) = light {
+                let color = light.color * light.intensity;
+                self.directional_shader.set_uniform3f32("lightColor", color.x, color.y, color.z);
+                self.directional_shader.set_uniform3f32("lightDirection", light.direction.x, light.direction.y, light.direction.z);
+                self.fullscreen();
+            }
+        }
+        
+        // light volumes: only the back faces are drawn, where they're behind the scene's surfaces
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthFunc(gl::GEQUAL);
+            gl::Enable(gl::CULL_FACE);
+            gl::CullFace(gl::FRONT);
+            gl::Enable(gl::DEPTH_CLAMP);
+        }
+        setup(&self.point_shader);
+        for light in lights {
+            if let Light::Point(light) = light {
+                set_light(&self.point_shader, light.color * light.intensity, light.position, light.range);
+                let model = Matrix4::from_translation(light.position) * Matrix4::from_scale(light.range * VOLUME_SCALE);
+                self.sphere.render(&self.point_shader, true, gl::TRIANGLES, camera.combined * model);
+            }
+        }
+        
+        setup(&self.spot_shader);
+        for light in lights {
+            if let Light::Spot(light) = light {
+                set_light(&self.spot_shader, light.color * light.intensity, light.position, light.range);
+                let (cos_inner, cos_outer) = light.cone_cosines();
+                let direction = light.direction.normalize();
+                self.spot_shader.set_uniform3f32("lightDirection", direction.x, direction.y, direction.z);
+                self.spot_shader.set_uniform1f32("cosInner", cos_inner);
+                self.spot_shader.set_uniform1f32("cosOuter", cos_outer);
+                
+                self.cone.render(&self.spot_shader, true, gl::TRIANGLES, camera.combined * spot_volume(light));
+            }
+        }
+        self.spot_shader.unbind();
+        
+        self.output.unbind();
+        state.restore();
+    }
+    
+    fn fullscreen(&self) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+        }
+    }
+    
+    /// Draws a single channel of the G-buffer over the current framebuffer, for debugging.
+    pub fn draw_debug(&self, view: GBufferView, camera: &Camera) {
+        let channel = match view {
+            GBufferView::Albedo => 0,
+            GBufferView::Normal => 1,
+            GBufferView::Metallic => 2,
+            GBufferView::Roughness => 3,
+            GBufferView::Occlusion => 4,
+            GBufferView::Depth => 5,
+        };
+        
+        let depth_test = unsafe { gl::IsEnabled(gl::DEPTH_TEST) };
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+        }
+        self.debug_shader.bind();
+        self.gbuffer.bind_textures(&self.debug_shader, 0);
+        self.debug_shader.set_uniform1i32("channel", channel);
+        self.debug_shader.set_uniform1f32("near", camera.near);
+        self.debug_shader.set_uniform1f32("far", camera.far);
+        self.debug_shader.set_uniform1i32("perspective", camera.is_perspective as i32);
+        self.fullscreen();
+        self.debug_shader.unbind();
+        if depth_test == gl::TRUE {
+            unsafe {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+        }
+    }
+    
+    pub fn gbuffer(&self) -> &GBuffer {
+        &self.gbuffer
+    }
+    
+    /// HDR result of the lighting pass in color attachment 0, with the scene's depth
+    pub fn output(&self) -> &Framebuffer {
+        &self.output
+    }
+}
+impl Drop for DeferredRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+fn light_shader(kind: &str, vertex: &str) -> ShaderProgram {
+    let mut shader = ShaderProgram::new();
+    shader.define(kind, "1");
+    shader.create_vertex_shader(vertex);
+    shader.include(shaders::LIGHTING_GLSL);
+    shader.create_fragment_shader(shaders::DEFERRED_LIGHT_FRAG);
+    shader.link();
+    
+    shader
+}
+
+fn set_light(shader: &ShaderProgram, color: Vector3<f32>, position: Vector3<f32>, range: f32) {
+    shader.set_uniform3f32("lightColor", color.x, color.y, color.z);
+    shader.set_uniform3f32("lightPosition", position.x, position.y, position.z);
+    shader.set_uniform1f32("lightRange", range);
+}
+
+/// Transform of the unit cone (apex at the origin, base at z = -1) enclosing `light`
+fn spot_volume(light: &SpotLight) -> Matrix4<f32> {
+    let direction = light.direction.normalize();
+    let up = if direction.y.abs() > 0.99 { Vector3::unit_z() } else { Vector3::unit_y() };
+    let position = Point3::from_vec(light.position);
+    let placement = Matrix4::look_at_rh(position, position + direction, up).invert().unwrap();
+    let radius = light.range * (light.outer_angle.clamp(1.0, 170.0).to_radians() * 0.5).tan() * VOLUME_SCALE;
+    
+    placement * Matrix4::from_nonuniform_scale(radius, radius, light.range)
+}
+
+/// GL state changed by the lighting pass
+struct LightingState {
+    depth_test: GLboolean,
+    blend: GLboolean,
+    cull_face: GLboolean,
+    depth_clamp: GLboolean,
+    depth_mask: GLboolean,
+    depth_func: GLint,
+    cull_face_mode: GLint,
+    blend_func: [GLint; 4],
+    blend_equation: [GLint; 2],
+}
+impl LightingState {
+    fn save() -> Self {
+        unsafe {
+            let mut state = Self {
+                depth_test: gl::IsEnabled(gl::DEPTH_TEST),
+                blend: gl::IsEnabled(gl::BLEND),
+                cull_face: gl::IsEnabled(gl::CULL_FACE),
+                depth_clamp: gl::IsEnabled(gl::DEPTH_CLAMP),
+                depth_mask: 0,
+                depth_func: 0,
+                cull_face_mode: 0,
+                blend_func: [0; 4],
+                blend_equation: [0; 2],
+            };
+            gl::GetBooleanv(gl::DEPTH_WRITEMASK, &mut state.depth_mask);
+            gl::GetIntegerv(gl::DEPTH_FUNC, &mut state.depth_func);
+            gl::GetIntegerv(gl::CULL_FACE_MODE, &mut state.cull_face_mode);
+            for (i, param) in [gl::BLEND_SRC_RGB, gl::BLEND_DST_RGB, gl::BLEND_SRC_ALPHA, gl::BLEND_DST_ALPHA].into_iter().enumerate() {
+                gl::GetIntegerv(param, &mut state.blend_func[i]);
+            }
+            gl::GetIntegerv(gl::BLEND_EQUATION_RGB, &mut state.blend_equation[0]);
+            gl::GetIntegerv(gl::BLEND_EQUATION_ALPHA, &mut state.blend_equation[1]);
+            
+            state
+        }
+    }
+    
+    fn restore(&self) {
+        let set = |cap: GLenum, enabled: GLboolean| unsafe {
+            if enabled == gl::TRUE {
+                gl::Enable(cap);
+            } else {
+                gl::Disable(cap);
+            }
+        };
+        set(gl::DEPTH_TEST, self.depth_test);
+        set(gl::BLEND, self.blend);
+        set(gl::CULL_FACE, self.cull_face);
+        set(gl::DEPTH_CLAMP, self.depth_clamp);
+        unsafe {
+            gl::DepthMask(self.depth_mask);
+            gl::DepthFunc(self.depth_func as GLenum);
+            gl::CullFace(self.cull_face_mode as GLenum);
+            let [src_rgb, dst_rgb, src_alpha, dst_alpha] = self.blend_func.map(|func| func as GLenum);
+            gl::BlendFuncSeparate(src_rgb, dst_rgb, src_alpha, dst_alpha);
+            gl::BlendEquationSeparate(self.blend_equation[0] as GLenum, self.blend_equation[1] as GLenum);
+        }
+    }
+}
+
+/// Adds triangle `a b c` to a positions-only mesh, wound counter-clockwise when seen from outside,
+/// i.e. from the side away from `center`.
+fn push_triangle(mesh: &mut Mesh, a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>, center: Vector3<f32>) {
+    let normal = (b - a).cross(c - a);
+    let outside = (a + b + c) / 3.0 - center;
+    let (b, c) = if normal.dot(outside) < 0.0 { (c, b) } else { (b, c) };
+    for v in [a, b, c] {
+        mesh.vertex(&[v.x, v.y, v.z]);
+    }
+}
+
+/// Unit UV sphere
+fn sphere_mesh(segments: u32, rings: u32) -> Mesh {
+    let mut mesh = Mesh::new(VertexAttributes::with(true, false, false, false));
+    let point = |segment: u32, ring: u32| {
+        let theta = std::f32::consts::PI * ring as f32 / rings as f32;
+        let phi = std::f32::consts::TAU * segment as f32 / segments as f32;
+        
+        vec3(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin())
+    };
+    
+    let center = vec3(0.0, 0.0, 0.0);
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let (a, b) = (point(segment, ring), point(segment + 1, ring));
+            let (c, d) = (point(segment, ring + 1), point(segment + 1, ring + 1));
+            if ring > 0 {
+                push_triangle(&mut mesh, a, b, d, center);
+            }
+            if ring < rings - 1 {
+                push_triangle(&mut mesh, a, d, c, center);
+            }
+        }
+    }
+    
+    mesh
+}
+
+/// Unit cone with its apex at the origin, opening towards -z, with a base of radius 1 at z = -1
+fn cone_mesh(segments: u32) -> Mesh {
+    let mut mesh = Mesh::new(VertexAttributes::with(true, false, false, false));
+    let point = |segment: u32| {
+        let phi = std::f32::consts::TAU * segment as f32 / segments as f32;
+        
+        vec3(phi.cos(), phi.sin(), -1.0)
+    };
+    
+    let apex = vec3(0.0, 0.0, 0.0);
+    let base = vec3(0.0, 0.0, -1.0);
+    let center = vec3(0.0, 0.0, -0.5);
+    for segment in 0..segments {
+        let (a, b) = (point(segment), point(segment + 1));
+        push_triangle(&mut mesh, apex, a, b, center);
+        push_triangle(&mut mesh, base, b, a, center);
+    }
+    
+    mesh
+}
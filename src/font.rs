@@ -1,6 +1,7 @@
 use std::collections::HashMap;
-use std::mem::swap;
+use std::str::FromStr;
 use crate::graphics::{Texture, TextureRenderer};
+use crate::sprite::TextureRegion;
 
 #[derive(Copy, Clone, Default, Debug, PartialEq)]
 pub struct Glyph {
@@ -26,7 +27,13 @@ pub struct BitmapFont {
     pub spacing: f32,
 }
 impl BitmapFont {
+    /// Panics if `fnt_data` is malformed, see `try_new`.
     pub fn new(tex: Texture, fnt_data: &str) -> Self {
+        Self::try_new(tex, fnt_data).unwrap()
+    }
+    
+    /// Parses an AngelCode BMFont text file for the glyphs in `tex`.
+    pub fn try_new(tex: Texture, fnt_data: &str) -> Result<Self, String> {
         let mut font = Self {
             tex,
             glyphs: Default::default(),
@@ -37,7 +44,7 @@ impl BitmapFont {
             spacing: 0.0
         };
         
-        for line in fnt_data.lines() {
+        for (line_no, line) in fnt_data.lines().enumerate() {
             let line_parts: Vec<&str> = line.split_whitespace().collect();
             
             match *line_parts.first().unwrap_or(&"") {
@@ -52,33 +59,32 @@ impl BitmapFont {
                 },
                 "char" => {
                     let mut glyph = Glyph::default();
-                    let mut x = 0f32;
-                    let mut y = 0f32;
+                    let mut x = 0u32;
+                    let mut y = 0u32;
                     for part in line_parts {
                         let pair = part.split_once("=").unwrap_or((part, ""));
                         
                         match pair {
-                            ("id", val) => glyph.id = char::from_u32(val.parse::<u32>().unwrap()).unwrap_or(char::from(val.parse::<u8>().unwrap())),
-                            ("x", val) => x = val.parse().unwrap(),
-                            ("y", val) => y = val.parse().unwrap(),
-                            ("width", val) => glyph.width = val.parse().unwrap(),
-                            ("height", val) => glyph.height = val.parse().unwrap(),
-                            ("xoffset", val) => glyph.x_offset = val.parse().unwrap(),
-                            ("yoffset", val) => glyph.y_offset = val.parse().unwrap(),
-                            ("xadvance", val) => glyph.x_advance = val.parse().unwrap(),
+                            ("id", val) => {
+                                let id = parse_value(line_no, "id", val)?;
+                                glyph.id = char::from_u32(id).ok_or(format!("Invalid character id {} on line {}", id, line_no + 1))?;
+                            },
+                            ("x", val) => x = parse_value(line_no, "x", val)?,
+                            ("y", val) => y = parse_value(line_no, "y", val)?,
+                            ("width", val) => glyph.width = parse_value(line_no, "width", val)?,
+                            ("height", val) => glyph.height = parse_value(line_no, "height", val)?,
+                            ("xoffset", val) => glyph.x_offset = parse_value(line_no, "xoffset", val)?,
+                            ("yoffset", val) => glyph.y_offset = parse_value(line_no, "yoffset", val)?,
+                            ("xadvance", val) => glyph.x_advance = parse_value(line_no, "xadvance", val)?,
                             _ => ()
                         }
                     }
                     
-                    glyph.u = x / (font.tex.width as f32);
-                    glyph.v = y / (font.tex.height as f32);
-                    glyph.u2 = (x + glyph.width) / (font.tex.width as f32);
-                    glyph.v2 = (y + glyph.height) / (font.tex.height as f32);
-                    
-                    glyph.v = -glyph.v + (font.tex.height as f32);
-                    glyph.v2 = -glyph.v2 + (font.tex.height as f32);
-                    
-                    swap(&mut glyph.v, &mut glyph.v2);
+                    let region = TextureRegion::new(&font.tex, x, y, glyph.width as u32, glyph.height as u32);
+                    glyph.u = region.u;
+                    glyph.v = region.v;
+                    glyph.u2 = region.u2;
+                    glyph.v2 = region.v2;
                     
                     font.glyphs.insert(glyph.id, glyph);
                 },
@@ -86,7 +92,18 @@ impl BitmapFont {
             }
         }
         
-        font
+        Ok(font)
+    }
+    
+    /// File name of the first texture page (`page id=0 file="font.png"`), relative to the .fnt file
+    pub fn page_file(fnt_data: &str) -> Option<&str> {
+        let line = fnt_data.lines().find(|line| line.starts_with("page "))?;
+        let value = &line[line.find(" file=")? + " file=".len()..];
+        // quoted names may contain spaces
+        match value.strip_prefix('"') {
+            Some(quoted) => quoted.split('"').next(),
+            None => value.split_whitespace().next(),
+        }
     }
     
     pub fn render<'a>(&'a self, tr: &mut TextureRenderer<'a>, text: &str, x: f32, y: f32, r: f32, g: f32, b: f32, a: f32) {
@@ -101,6 +118,6 @@ impl BitmapFont {
     }
 }
 
-
-
-
+fn parse_value<T: FromStr>(line_no: usize, key: &str, val: &str) -> Result<T, String> {
+    val.parse().map_err(|_| format!("Invalid value {:?} for {} on line {}", val, key, line_no + 1))
+}
@@ -0,0 +1,452 @@
+use std::cell::Cell;
+use std::fmt::{Display, Formatter};
+use gl::types::*;
+use crate::graphics::{GlTexture, Texture, TextureFormat, TextureParams};
+use crate::texture::Texture2DMultisample;
+
+/// Render target storage which can't be sampled, cheaper than a texture for depth buffers that are only
+/// used for depth testing.
+pub struct Renderbuffer {
+    id: GLuint,
+    pub width: u32,
+    pub height: u32,
+    /// Sample count, 0 if not multisampled
+    pub samples: u32,
+    format: TextureFormat,
+}
+impl Renderbuffer {
+    pub fn new(width: u32, height: u32, format: TextureFormat) -> Self {
+        Self::multisampled(width, height, format, 0)
+    }
+    
+    /// A multisampled renderbuffer, `samples` is clamped to the driver's maximum for `format`.
+    pub fn multisampled(width: u32, height: u32, format: TextureFormat, samples: u32) -> Self {
+        let mut max = 0;
+        if samples > 0 {
+            unsafe {
+                gl::GetInternalformativ(gl::RENDERBUFFER, format.internal_format(), gl::SAMPLES, 1, &mut max);
+            }
+        }
+        let samples = samples.min(max as u32);
+        
+        let mut id = 0;
+        unsafe {
+            gl::GenRenderbuffers(1, &mut id);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, id);
+            gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples as GLsizei, format.internal_format(), width as GLsizei, height as GLsizei);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+        }
+        
+        Self {
+            id,
+            width,
+            height,
+            samples,
+            format,
+        }
+    }
+    
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+    
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+}
+impl Drop for Renderbuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteRenderbuffers(1, &self.id);
+        }
+    }
+}
+
+pub enum Attachment {
+    Texture(Texture),
+    Renderbuffer(Renderbuffer),
+    Multisample(Texture2DMultisample),
+}
+impl Attachment {
+    pub fn texture(&self) -> Option<&Texture> {
+        match self {
+            Attachment::Texture(tex) => Some(tex),
+            _ => None,
+        }
+    }
+    
+    pub fn format(&self) -> TextureFormat {
+        match self {
+            Attachment::Texture(tex) => tex.format(),
+            Attachment::Renderbuffer(rb) => rb.format,
+            Attachment::Multisample(tex) => tex.format(),
+        }
+    }
+    
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            Attachment::Texture(tex) => (tex.width, tex.height),
+            Attachment::Renderbuffer(rb) => (rb.width, rb.height),
+            Attachment::Multisample(tex) => (tex.width, tex.height),
+        }
+    }
+    
+    /// Sample count, 0 if not multisampled
+    pub fn samples(&self) -> u32 {
+        match self {
+            Attachment::Texture(_) => 0,
+            Attachment::Renderbuffer(rb) => rb.samples,
+            Attachment::Multisample(tex) => tex.samples,
+        }
+    }
+    
+    /// A new attachment of the same kind and format with a different size
+    fn resized(&self, width: u32, height: u32) -> Self {
+        match self {
+            Attachment::Texture(tex) => Attachment::Texture(Texture::empty(width, height, tex.format(), *tex.params())),
+            Attachment::Renderbuffer(rb) => Attachment::Renderbuffer(Renderbuffer::multisampled(width, height, rb.format, rb.samples)),
+            Attachment::Multisample(tex) => Attachment::Multisample(Texture2DMultisample::new(width, height, tex.format(), tex.samples)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FramebufferError {
+    /// An attachment is incomplete, e.g. a format which isn't color-renderable
+    IncompleteAttachment,
+    /// No color, depth or stencil attachment
+    MissingAttachment,
+    IncompleteDrawBuffer,
+    IncompleteReadBuffer,
+    /// The combination of attachment formats isn't supported by the driver
+    Unsupported,
+    /// Attachments have different sample counts
+    IncompleteMultisample,
+    IncompleteLayerTargets,
+    Unknown(GLenum),
+    SizeMismatch { expected: (u32, u32), found: (u32, u32) },
+    /// A depth format used as a color attachment, or the reverse
+    WrongFormat(TextureFormat),
+    TooManyAttachments(usize),
+}
+impl Display for FramebufferError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FramebufferError::IncompleteAttachment => write!(f, "Framebuffer has an incomplete attachment"),
+            FramebufferError::MissingAttachment => write!(f, "Framebuffer has no attachments"),
+            FramebufferError::IncompleteDrawBuffer => write!(f, "Framebuffer draw buffer has no attachment"),
+            FramebufferError::IncompleteReadBuffer => write!(f, "Framebuffer read buffer has no attachment"),
+            FramebufferError::Unsupported => write!(f, "Framebuffer attachment formats are not supported by the driver"),
+            FramebufferError::IncompleteMultisample => write!(f, "Framebuffer attachments have different sample counts"),
+            FramebufferError::IncompleteLayerTargets => write!(f, "Framebuffer attachments have different layer counts"),
+            FramebufferError::Unknown(status) => write!(f, "Framebuffer is incomplete (status {:#x})", status),
+            FramebufferError::SizeMismatch { expected, found } => write!(f, "Attachment is {}x{}, but the framebuffer is {}x{}", found.0, found.1, expected.0, expected.1),
+            FramebufferError::WrongFormat(format) => write!(f, "Format {:?} can't be used for this attachment", format),
+            FramebufferError::TooManyAttachments(max) => write!(f, "The driver supports at most {} color attachments", max),
+        }
+    }
+}
+impl std::error::Error for FramebufferError {}
+
+/// An offscreen render target with any number of color attachments and an optional depth (and stencil)
+/// attachment.
+///
+/// `bind` redirects rendering to it and sets the viewport to its size; `unbind` restores the previous
+/// framebuffer and viewport. Texture attachments can then be drawn like any other texture, e.g. with
+/// `TextureRenderer`. Like every texture, their rows are stored bottom to top.
+pub struct Framebuffer {
+    id: GLuint,
+    pub width: u32,
+    pub height: u32,
+    colors: Vec<Attachment>,
+    depth: Option<Attachment>,
+    /// Framebuffer binding and viewport from before `bind`
+    previous: Cell<Option<(GLint, [GLint; 4])>>,
+}
+impl Framebuffer {
+    /// Creates a framebuffer without attachments, see `attach_color` and `attach_depth`.
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::CreateFramebuffers(1, &mut id);
+        }
+        
+        let fb = Self {
+            id,
+            width,
+            height,
+            colors: Vec::new(),
+            depth: None,
+            previous: Cell::new(None),
+        };
+        // until a color attachment is added, e.g. for depth-only shadow maps
+        fb.update_draw_buffers();
+        
+        fb
+    }
+    
+    /// The common case: a single linearly filtered color texture, and a depth renderbuffer if `depth` is set.
+    pub fn create(width: u32, height: u32, color: TextureFormat, depth: Option<TextureFormat>) -> Result<Self, FramebufferError> {
+        let mut fb = Self::new(width, height);
+        fb.attach_color(Attachment::Texture(Texture::empty(width, height, color, TextureParams::linear())))?;
+        if let Some(depth) = depth {
+            fb.attach_depth(Attachment::Renderbuffer(Renderbuffer::new(width, height, depth)))?;
+        }
+        fb.check()?;
+        
+        Ok(fb)
+    }
+    
+    /// A multisampled framebuffer with a color renderbuffer, and a depth renderbuffer if `depth` is set.
+    /// Render to it, then `resolve_to` a regular framebuffer of the same size to use the result as a texture.
+    pub fn create_multisampled(width: u32, height: u32, color: TextureFormat, depth: Option<TextureFormat>, samples: u32) -> Result<Self, FramebufferError> {
+        let mut fb = Self::new(width, height);
+        fb.attach_color(Attachment::Renderbuffer(Renderbuffer::multisampled(width, height, color, samples)))?;
+        if let Some(depth) = depth {
+            fb.attach_depth(Attachment::Renderbuffer(Renderbuffer::multisampled(width, height, depth, samples)))?;
+        }
+        fb.check()?;
+        
+        Ok(fb)
+    }
+    
+    /// Adds a color attachment, returning its index (the fragment shader output location).
+    pub fn attach_color(&mut self, attachment: Attachment) -> Result<usize, FramebufferError> {
+        self.check_attachment(&attachment)?;
+        if attachment.format().is_depth() {
+            return Err(FramebufferError::WrongFormat(attachment.format()));
+        }
+        
+        let mut max = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_COLOR_ATTACHMENTS, &mut max);
+        }
+        if self.colors.len() >= max as usize {
+            return Err(FramebufferError::TooManyAttachments(max as usize));
+        }
+        
+        let index = self.colors.len();
+        self.attach(gl::COLOR_ATTACHMENT0 + index as GLenum, &attachment);
+        self.colors.push(attachment);
+        self.update_draw_buffers();
+        
+        Ok(index)
+    }
+    
+    /// Sets the depth attachment, which also serves as the stencil attachment for depth/stencil formats.
+    pub fn attach_depth(&mut self, attachment: Attachment) -> Result<(), FramebufferError> {
+        self.check_attachment(&attachment)?;
+        if !attachment.format().is_depth() {
+            return Err(FramebufferError::WrongFormat(attachment.format()));
+        }
+        
+        if let Some(old) = &self.depth {
+            if old.format().has_stencil() && !attachment.format().has_stencil() {
+                unsafe {
+                    gl::NamedFramebufferRenderbuffer(self.id, gl::STENCIL_ATTACHMENT, gl::RENDERBUFFER, 0);
+                }
+            }
+        }
+        
+        let point = if attachment.format().has_stencil() { gl::DEPTH_STENCIL_ATTACHMENT } else { gl::DEPTH_ATTACHMENT };
+        self.attach(point, &attachment);
+        self.depth = Some(attachment);
+        
+        Ok(())
+    }
+    
+    fn check_attachment(&self, attachment: &Attachment) -> Result<(), FramebufferError> {
+        if attachment.size() != (self.width, self.height) {
+            return Err(FramebufferError::SizeMismatch { expected: (self.width, self.height), found: attachment.size() });
+        }
+        
+        Ok(())
+    }
+    
+    fn attach(&self, point: GLenum, attachment: &Attachment) {
+        unsafe {
+            match attachment {
+                Attachment::Texture(tex) => gl::NamedFramebufferTexture(self.id, point, tex.id(), 0),
+                Attachment::Renderbuffer(rb) => gl::NamedFramebufferRenderbuffer(self.id, point, gl::RENDERBUFFER, rb.id),
+                Attachment::Multisample(tex) => gl::NamedFramebufferTexture(self.id, point, tex.id(), 0),
+            }
+        }
+    }
+    
+    fn update_draw_buffers(&self) {
+        unsafe {
+            if self.colors.is_empty() {
+                gl::NamedFramebufferDrawBuffer(self.id, gl::NONE);
+                gl::NamedFramebufferReadBuffer(self.id, gl::NONE);
+            } else {
+                let buffers: Vec<GLenum> = (0..self.colors.len() as GLenum).map(|i| gl::COLOR_ATTACHMENT0 + i).collect();
+                gl::NamedFramebufferDrawBuffers(self.id, buffers.len() as GLsizei, buffers.as_ptr());
+                gl::NamedFramebufferReadBuffer(self.id, gl::COLOR_ATTACHMENT0);
+            }
+        }
+    }
+    
+    /// Checks that the framebuffer can be rendered to.
+    pub fn check(&self) -> Result<(), FramebufferError> {
+        let status = unsafe { gl::CheckNamedFramebufferStatus(self.id, gl::FRAMEBUFFER) };
+        match status {
+            gl::FRAMEBUFFER_COMPLETE => Ok(()),
+            gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => Err(FramebufferError::IncompleteAttachment),
+            gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => Err(FramebufferError::MissingAttachment),
+            gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => Err(FramebufferError::IncompleteDrawBuffer),
+            gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => Err(FramebufferError::IncompleteReadBuffer),
+            gl::FRAMEBUFFER_UNSUPPORTED => Err(FramebufferError::Unsupported),
+            gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => Err(FramebufferError::IncompleteMultisample),
+            gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => Err(FramebufferError::IncompleteLayerTargets),
+            status => Err(FramebufferError::Unknown(status)),
+        }
+    }
+    
+    /// Recreates every attachment at the new size, keeping their formats. The previous contents are lost.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        if (width, height) == (self.width, self.height) {
+            return Ok(());
+        }
+        
+        self.width = width;
+        self.height = height;
+        
+        let colors: Vec<Attachment> = self.colors.iter().map(|a| a.resized(width, height)).collect();
+        for (i, attachment) in colors.iter().enumerate() {
+            self.attach(gl::COLOR_ATTACHMENT0 + i as GLenum, attachment);
+        }
+        self.colors = colors;
+        
+        if let Some(depth) = self.depth.take() {
+            self.attach_depth(depth.resized(width, height))?;
+        }
+        
+        self.check()
+    }
+    
+    /// Renders to this framebuffer from now on, with a viewport covering all of it.
+    pub fn bind(&self) {
+        unsafe {
+            let mut previous = 0;
+            let mut viewport = [0; 4];
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut previous);
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            self.previous.set(Some((previous, viewport)));
+            
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::Viewport(0, 0, self.width as GLsizei, self.height as GLsizei);
+        }
+    }
+    
+    /// Restores the framebuffer and viewport that were in use when `bind` was called.
+    pub fn unbind(&self) {
+        unsafe {
+            match self.previous.take() {
+                Some((previous, viewport)) => {
+                    gl::BindFramebuffer(gl::FRAMEBUFFER, previous as GLuint);
+                    gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+                },
+                None => gl::BindFramebuffer(gl::FRAMEBUFFER, 0),
+            }
+        }
+    }
+    
+    /// Clears every color attachment to the given color, and the depth/stencil attachment to 1 and 0.
+    /// Works whether or not the framebuffer is bound, but ignores the scissor test and write masks like `gl::Clear`.
+    pub fn clear(&self, r: f32, g: f32, b: f32, a: f32) {
+        let color = [r, g, b, a];
+        unsafe {
+            for (i, attachment) in self.colors.iter().enumerate() {
+                match attachment.format().format() {
+                    gl::RED_INTEGER | gl::RGBA_INTEGER => gl::ClearNamedFramebufferuiv(self.id, gl::COLOR, i as GLint, [0u32; 4].as_ptr()),
+                    _ => gl::ClearNamedFramebufferfv(self.id, gl::COLOR, i as GLint, color.as_ptr()),
+                }
+            }
+            
+            match &self.depth {
+                Some(depth) if depth.format().has_stencil() => gl::ClearNamedFramebufferfi(self.id, gl::DEPTH_STENCIL, 0, 1.0, 0),
+                Some(_) => gl::ClearNamedFramebufferfv(self.id, gl::DEPTH, 0, &1.0),
+                None => (),
+            }
+        }
+    }
+    
+    /// Resolves the multisampled attachments into `target`, which must have the same size. Each color
+    /// attachment is resolved into the target's attachment with the same index, and depth is copied if
+    /// both framebuffers have a depth attachment with the same format.
+    ///
+    /// Also works as a plain copy between framebuffers without multisampling.
+    pub fn resolve_to(&self, target: &Framebuffer) {
+        if (self.width, self.height) != (target.width, target.height) {
+            panic!("Can't resolve a {}x{} framebuffer into a {}x{} one", self.width, self.height, target.width, target.height);
+        }
+        
+        for i in 0..self.colors.len().min(target.colors.len()) {
+            let attachment = gl::COLOR_ATTACHMENT0 + i as GLenum;
+            unsafe {
+                gl::NamedFramebufferReadBuffer(self.id, attachment);
+                gl::NamedFramebufferDrawBuffer(target.id, attachment);
+            }
+            self.blit(target.id, gl::COLOR_BUFFER_BIT);
+        }
+        self.update_draw_buffers();
+        target.update_draw_buffers();
+        
+        if let (Some(depth), Some(target_depth)) = (&self.depth, &target.depth) {
+            if depth.format() == target_depth.format() {
+                let mask = if depth.format().has_stencil() { gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT } else { gl::DEPTH_BUFFER_BIT };
+                self.blit(target.id, mask);
+            }
+        }
+    }
+    
+    /// Resolves the first color attachment onto the window, which must be the same size as this framebuffer.
+    pub fn resolve_to_screen(&self) {
+        self.blit(0, gl::COLOR_BUFFER_BIT);
+    }
+    
+    fn blit(&self, target: GLuint, mask: GLbitfield) {
+        let (width, height) = (self.width as GLint, self.height as GLint);
+        unsafe {
+            gl::BlitNamedFramebuffer(self.id, target, 0, 0, width, height, 0, 0, width, height, mask, gl::NEAREST);
+        }
+    }
+    
+    /// Sample count of the attachments, 0 if not multisampled
+    pub fn samples(&self) -> u32 {
+        self.colors.first().or(self.depth.as_ref()).map(Attachment::samples).unwrap_or(0)
+    }
+    
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+    
+    /// Color texture at `index`. Panics if that attachment is a renderbuffer or multisampled.
+    pub fn color(&self, index: usize) -> &Texture {
+        self.colors[index].texture().expect("Color attachment is a renderbuffer, not a texture")
+    }
+    
+    pub fn color_attachment(&self, index: usize) -> &Attachment {
+        &self.colors[index]
+    }
+    
+    pub fn color_count(&self) -> usize {
+        self.colors.len()
+    }
+    
+    /// Depth texture, if the depth attachment is a texture
+    pub fn depth(&self) -> Option<&Texture> {
+        self.depth.as_ref().and_then(Attachment::texture)
+    }
+    
+    pub fn depth_attachment(&self) -> Option<&Attachment> {
+        self.depth.as_ref()
+    }
+}
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+        }
+    }
+}
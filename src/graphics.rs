@@ -4,9 +4,26 @@ use strum_macros::EnumIter;
 use cgmath::Matrix4;
 use std::collections::HashMap;
 use std::ffi::c_void;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use gl::types::*;
-use image::RgbaImage;
+use image::{DynamicImage, GenericImageView, RgbaImage};
+use crate::compressed::CompressedFormat;
+use crate::shaders;
+use crate::sprite::TextureRegion;
+
+/// Attribute location of vertex positions (vec3). Custom shaders should use `layout(location = ...)` with these.
+pub const ATTRIB_POSITION: u32 = 0;
+/// Attribute location of vertex colors (vec4)
+pub const ATTRIB_COLOR: u32 = 1;
+/// Attribute location of vertex normals (vec3)
+pub const ATTRIB_NORMAL: u32 = 2;
+/// Attribute location of texture coordinates (vec2)
+pub const ATTRIB_TEX_COORD: u32 = 3;
+
+/// Uniform set by `Mesh::render` to the combined projection/model/view matrix (mat4)
+pub const UNIFORM_PROJ_MODEL_VIEW: &str = "projModelView";
+/// Uniform set by `TextureRenderer` to the texture unit of the current texture (sampler2D)
+pub const UNIFORM_TEXTURE_SAMPLER: &str = "textureSampler";
 
 #[derive(PartialEq, EnumIter, Clone, Copy)]
 pub enum Usage {
@@ -19,10 +36,10 @@ pub enum Usage {
 impl Usage {
     pub fn position(&self) -> u8 {
         match *self {
-            Usage::POSITIONS => 0,
-            Usage::COLORS => 1,
-            Usage::NORMALS => 2,
-            Usage::TEXCOORDS => 3,
+            Usage::POSITIONS => ATTRIB_POSITION as u8,
+            Usage::COLORS => ATTRIB_COLOR as u8,
+            Usage::NORMALS => ATTRIB_NORMAL as u8,
+            Usage::TEXCOORDS => ATTRIB_TEX_COORD as u8,
             Usage::INDICES => 4,
         }
     }
@@ -319,6 +336,12 @@ pub struct ShaderProgram {
     vertex_shader_id: GLuint,
     fragment_shader_id: GLuint,
     uniforms: HashMap<String, GLint>,
+    defines: Vec<(String, String)>,
+    includes: Vec<String>,
+    sources: Vec<(GLenum, String)>,
+    binary_cache: Option<PathBuf>,
+    /// First compile error, reported by `link`
+    error: Option<String>,
     pub linked: bool,
 }
 impl ShaderProgram {
@@ -329,20 +352,95 @@ impl ShaderProgram {
                 vertex_shader_id: 0,
                 fragment_shader_id: 0,
                 uniforms: HashMap::new(),
+                defines: Vec::new(),
+                includes: Vec::new(),
+                sources: Vec::new(),
+                binary_cache: None,
+                error: None,
                 linked: false
             }
         }
     }
     
+    /// Stores the linked program binary in `dir`, and reuses it on later runs instead of compiling.
+    ///
+    /// Compilation is deferred until `link()` so the sources can be hashed first. Binaries are keyed by
+    /// the shader sources, defines, and the driver's vendor/renderer/version strings. If the driver rejects
+    /// a cached binary, the sources are compiled and the cache entry is replaced.
+    pub fn with_binary_cache(mut self, dir: &Path) -> Self {
+        self.binary_cache = Some(dir.to_path_buf());
+        
+        self
+    }
+    
+    /// Adds a `#define name value` to every shader created after this call. Inserted after the `#version` line.
+    pub fn define(&mut self, name: &str, value: &str) {
+        self.defines.push((name.to_owned(), value.to_owned()));
+    }
+    
+    /// Adds a block of GLSL, e.g. `shaders::SHADOW_PCF_GLSL`, to every shader created after this call.
+    /// Inserted after the `#version` line and defines. Call it after `create_vertex_shader` for
+    /// fragment-only code.
+    pub fn include(&mut self, code: &str) {
+        self.includes.push(code.to_owned());
+    }
+    
     pub fn create_vertex_shader(&mut self, code: &str) {
-        self.vertex_shader_id = Self::create_shader(code, gl::VERTEX_SHADER, self.program_id);
+        let code = Self::preprocess(code, &self.defines, &self.includes);
+        if self.binary_cache.is_none() {
+            match Self::create_shader(&code, gl::VERTEX_SHADER, self.program_id) {
+                Ok(id) => self.vertex_shader_id = id,
+                Err(err) => self.error = self.error.take().or(Some(err)),
+            }
+        }
+        self.sources.push((gl::VERTEX_SHADER, code));
     }
     
     pub fn create_fragment_shader(&mut self, code: &str) {
-        self.fragment_shader_id = Self::create_shader(code, gl::FRAGMENT_SHADER, self.program_id);
+        let code = Self::preprocess(code, &self.defines, &self.includes);
+        if self.binary_cache.is_none() {
+            match Self::create_shader(&code, gl::FRAGMENT_SHADER, self.program_id) {
+                Ok(id) => self.fragment_shader_id = id,
+                Err(err) => self.error = self.error.take().or(Some(err)),
+            }
+        }
+        self.sources.push((gl::FRAGMENT_SHADER, code));
     }
     
-    fn create_shader(code: &str, shader_type: GLenum, program_id: GLuint) -> GLuint {
+    fn preprocess(code: &str, defines: &[(String, String)], includes: &[String]) -> String {
+        if defines.is_empty() && includes.is_empty() {
+            return code.to_owned();
+        }
+        
+        let mut block = String::new();
+        for (name, value) in defines {
+            block.push_str(&format!("#define {} {}\n", name, value));
+        }
+        for include in includes {
+            block.push_str(include);
+            if !include.ends_with('\n') {
+                block.push('\n');
+            }
+        }
+        
+        // #version must remain the first statement, so defines go directly after it
+        match code.find("#version") {
+            Some(start) => {
+                let end = code[start..].find('\n').map(|i| start + i + 1).unwrap_or(code.len());
+                let mut out = code[..end].to_owned();
+                if !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push_str(&block);
+                out.push_str(&code[end..]);
+                
+                out
+            },
+            None => block + code
+        }
+    }
+    
+    fn create_shader(code: &str, shader_type: GLenum, program_id: GLuint) -> Result<GLuint, String> {
         unsafe {
             let id = gl::CreateShader(shader_type);
             if id == 0 {
@@ -355,20 +453,64 @@ impl ShaderProgram {
             gl::CompileShader(id);
             
             if Self::getsiv(id, gl::COMPILE_STATUS) == 0 {
-                panic!("Error compiling shader code: {}", Self::getslog(id));
+                let log = Self::getslog(id);
+                gl::DeleteShader(id);
+                return Err(format!("Error compiling shader code: {}", log));
             }
             
             gl::AttachShader(program_id, id);
             
-            id
+            Ok(id)
         }
     }
     
+    /// Links the program, panicking with the compiler or linker log if a shader is invalid. See `try_link`.
     pub fn link(&mut self) {
+        if let Err(err) = self.try_link() {
+            panic!("{}", err);
+        }
+    }
+    
+    /// Same as `link`, returning compile and link errors instead of panicking.
+    pub fn try_link(&mut self) -> Result<(), String> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        
+        match self.binary_cache.clone() {
+            Some(dir) => {
+                let path = dir.join(format!("{:016x}.bin", self.cache_key()));
+                if self.load_binary(&path) {
+                    self.linked = true;
+                    return Ok(());
+                }
+                
+                for (shader_type, code) in &self.sources {
+                    let id = Self::create_shader(code, *shader_type, self.program_id)?;
+                    match *shader_type {
+                        gl::VERTEX_SHADER => self.vertex_shader_id = id,
+                        gl::FRAGMENT_SHADER => self.fragment_shader_id = id,
+                        _ => ()
+                    }
+                }
+                
+                unsafe {
+                    gl::ProgramParameteri(self.program_id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as GLint);
+                }
+                self.link_sources()?;
+                self.save_binary(&dir, &path);
+                
+                Ok(())
+            },
+            None => self.link_sources()
+        }
+    }
+    
+    fn link_sources(&mut self) -> Result<(), String> {
         unsafe {
             gl::LinkProgram(self.program_id);
             if Self::getpiv(self.program_id, gl::LINK_STATUS) == 0 {
-                panic!("Error linking shader code: {}", Self::getplog(self.program_id));
+                return Err(format!("Error linking shader code: {}", Self::getplog(self.program_id)));
             }
             
             if self.vertex_shader_id != 0 {
@@ -386,6 +528,64 @@ impl ShaderProgram {
             
             self.linked = true;
         }
+        
+        Ok(())
+    }
+    
+    fn cache_key(&self) -> u64 {
+        let mut hash = FNV_OFFSET;
+        for (shader_type, code) in &self.sources {
+            hash = fnv1a(hash, &shader_type.to_le_bytes());
+            hash = fnv1a(hash, code.as_bytes());
+        }
+        for (name, value) in &self.defines {
+            hash = fnv1a(hash, name.as_bytes());
+            hash = fnv1a(hash, value.as_bytes());
+        }
+        for param in [gl::VENDOR, gl::RENDERER, gl::VERSION] {
+            hash = fnv1a(hash, gl_string(param).as_bytes());
+        }
+        
+        hash
+    }
+    
+    fn load_binary(&self, path: &Path) -> bool {
+        let data = match std::fs::read(path) {
+            Ok(data) if data.len() > 4 => data,
+            _ => return false,
+        };
+        
+        // file layout: binary format (u32 LE) followed by the driver's program binary
+        let format = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let binary = &data[4..];
+        unsafe {
+            gl::ProgramBinary(self.program_id, format, binary.as_ptr() as *const c_void, binary.len() as GLsizei);
+        }
+        
+        Self::getpiv(self.program_id, gl::LINK_STATUS) != 0
+    }
+    
+    fn save_binary(&self, dir: &Path, path: &Path) {
+        let len = Self::getpiv(self.program_id, gl::PROGRAM_BINARY_LENGTH);
+        if len <= 0 {
+            return;
+        }
+        
+        let mut format: GLenum = 0;
+        let mut written: GLsizei = 0;
+        let mut binary = vec![0u8; len as usize];
+        unsafe {
+            gl::GetProgramBinary(self.program_id, len, &mut written, &mut format, binary.as_mut_ptr() as *mut c_void);
+        }
+        binary.truncate(written as usize);
+        
+        let mut data = Vec::with_capacity(binary.len() + 4);
+        data.extend_from_slice(&format.to_le_bytes());
+        data.extend_from_slice(&binary);
+        
+        if let Err(err) = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(path, data)) {
+            println!("Warning writing shader binary cache {:?}: {}", path, err);
+        }
     }
     
     pub fn bind(&self) {
@@ -420,6 +620,48 @@ impl ShaderProgram {
         }
     }
     
+    pub fn set_uniform2f32(&self, name: &str, x: f32, y: f32) {
+        unsafe {
+            gl::Uniform2f(Self::check_uniform(self, name), x, y);
+        }
+    }
+    
+    pub fn set_uniform3f32(&self, name: &str, x: f32, y: f32, z: f32) {
+        unsafe {
+            gl::Uniform3f(Self::check_uniform(self, name), x, y, z);
+        }
+    }
+    
+    pub fn set_uniform4f32(&self, name: &str, x: f32, y: f32, z: f32, w: f32) {
+        unsafe {
+            gl::Uniform4f(Self::check_uniform(self, name), x, y, z, w);
+        }
+    }
+    
+    /// Links the uniform block `name` to a binding point, e.g. `UniformBuffer::binding()`.
+    pub fn bind_uniform_block(&self, name: &str, binding: u32) {
+        let c_name = std::ffi::CString::new(name).unwrap();
+        unsafe {
+            let index = gl::GetUniformBlockIndex(self.program_id, c_name.as_ptr());
+            if index == gl::INVALID_INDEX {
+                panic!("Uniform block {} not found in shader program", name);
+            }
+            gl::UniformBlockBinding(self.program_id, index, binding);
+        }
+    }
+    
+    /// Links the shader storage block `name` to a binding point, e.g. `StorageBuffer::binding()`.
+    pub fn bind_storage_block(&self, name: &str, binding: u32) {
+        let c_name = std::ffi::CString::new(name).unwrap();
+        unsafe {
+            let index = gl::GetProgramResourceIndex(self.program_id, gl::SHADER_STORAGE_BLOCK, c_name.as_ptr());
+            if index == gl::INVALID_INDEX {
+                panic!("Shader storage block {} not found in shader program", name);
+            }
+            gl::ShaderStorageBlockBinding(self.program_id, index, binding);
+        }
+    }
+    
     fn check_uniform(&self, name: &str) -> GLint {
         if self.uniforms.contains_key(name) { // return existing uniform location
             return *self.uniforms.get(name).unwrap();
@@ -479,6 +721,130 @@ impl ShaderProgram {
     }
 }
 
+/// Lazily compiled permutations of a single shader source.
+///
+/// Each feature keyword is a bit in the feature set passed to `get()`; bit `i` compiles the shader with
+/// `#define <features[i]> 1`. Programs are cached per feature set, so each permutation is compiled once.
+pub struct ShaderVariants {
+    vertex_shader_code: String,
+    fragment_shader_code: String,
+    features: Vec<String>,
+    programs: HashMap<u32, ShaderProgram>,
+    binary_cache: Option<PathBuf>,
+}
+impl ShaderVariants {
+    /// Keyword enabled by `attribute_features()` for meshes with vertex colors
+    pub const VERTEX_COLORS: &'static str = "VERTEX_COLORS";
+    /// Keyword enabled by `attribute_features()` for meshes with normals
+    pub const NORMALS: &'static str = "NORMALS";
+    /// Keyword enabled by `attribute_features()` for meshes with texture coordinates
+    pub const TEXTURED: &'static str = "TEXTURED";
+    
+    pub fn new(vertex_shader_code: &str, fragment_shader_code: &str, features: &[&str]) -> Self {
+        if features.len() > 32 {
+            panic!("ShaderVariants supports at most 32 features, got {}", features.len());
+        }
+        
+        Self {
+            vertex_shader_code: vertex_shader_code.to_owned(),
+            fragment_shader_code: fragment_shader_code.to_owned(),
+            features: features.iter().map(|f| f.to_string()).collect(),
+            programs: HashMap::new(),
+            binary_cache: None,
+        }
+    }
+    
+    /// Uses `ShaderProgram::with_binary_cache` for every compiled variant.
+    pub fn with_binary_cache(mut self, dir: &Path) -> Self {
+        self.binary_cache = Some(dir.to_path_buf());
+        
+        self
+    }
+    
+    /// Returns the bit for `keyword`, or 0 if this shader doesn't declare it.
+    pub fn feature(&self, keyword: &str) -> u32 {
+        match self.features.iter().position(|f| f == keyword) {
+            Some(i) => 1 << i,
+            None => 0,
+        }
+    }
+    
+    pub fn features_from(&self, keywords: &[&str]) -> u32 {
+        keywords.iter().fold(0, |set, keyword| set | self.feature(keyword))
+    }
+    
+    /// Feature set matching the vertex data a mesh provides (see `VERTEX_COLORS`, `NORMALS` and `TEXTURED`).
+    pub fn attribute_features(&self, attribs: &VertexAttributes) -> u32 {
+        let mut set = 0;
+        if attribs.has_colors {
+            set |= self.feature(Self::VERTEX_COLORS);
+        }
+        if attribs.has_normals {
+            set |= self.feature(Self::NORMALS);
+        }
+        if attribs.has_tex_coords {
+            set |= self.feature(Self::TEXTURED);
+        }
+        
+        set
+    }
+    
+    /// Returns the program for `features`, compiling it first if this is the first request.
+    pub fn get(&mut self, features: u32) -> &ShaderProgram {
+        let features = if self.features.len() < 32 { features & ((1 << self.features.len()) - 1) } else { features };
+        
+        let Self { vertex_shader_code, fragment_shader_code, features: keywords, programs, binary_cache } = self;
+        programs.entry(features).or_insert_with(|| {
+            let mut shader = ShaderProgram::new();
+            if let Some(dir) = binary_cache {
+                shader = shader.with_binary_cache(dir);
+            }
+            for (i, keyword) in keywords.iter().enumerate() {
+                if features & (1 << i) != 0 {
+                    shader.define(keyword, "1");
+                }
+            }
+            shader.create_vertex_shader(vertex_shader_code);
+            shader.create_fragment_shader(fragment_shader_code);
+            shader.link();
+            
+            shader
+        })
+    }
+    
+    /// Number of permutations compiled so far
+    pub fn compiled(&self) -> usize {
+        self.programs.len()
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+/// 64-bit FNV-1a. Used instead of `DefaultHasher` because cache keys must be stable across builds.
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    
+    hash
+}
+
+fn ne_bytes<T: Copy, const N: usize>(data: &[T], to_bytes: fn(T) -> [u8; N]) -> Vec<u8> {
+    data.iter().flat_map(|val| to_bytes(*val)).collect()
+}
+
+fn gl_string(name: GLenum) -> String {
+    unsafe {
+        let ptr = gl::GetString(name);
+        if ptr.is_null() {
+            return String::new();
+        }
+        
+        std::ffi::CStr::from_ptr(ptr as *const std::os::raw::c_char).to_string_lossy().into_owned()
+    }
+}
+
 pub struct Mesh {
     vao: VertexArrayObject,
     attribs: VertexAttributes,
@@ -505,7 +871,7 @@ impl Mesh {
         }
         
         self.vao.bind();
-        shader.set_uniform_mat4f("projModelView", proj_model_view);
+        shader.set_uniform_mat4f(UNIFORM_PROJ_MODEL_VIEW, proj_model_view);
         self.vao.render(primitive);
         self.vao.unbind();
         
@@ -514,6 +880,16 @@ impl Mesh {
         }
     }
     
+    /// Renders with the permutation of `variants` matching this mesh's vertex attributes, plus any `extra_features`.
+    pub fn render_variant(&mut self, variants: &mut ShaderVariants, extra_features: u32, primitive: GLenum, proj_model_view: Matrix4<f32>) {
+        let features = variants.attribute_features(&self.attribs) | extra_features;
+        self.render(variants.get(features), false, primitive, proj_model_view);
+    }
+    
+    pub fn attributes(&self) -> VertexAttributes {
+        self.attribs
+    }
+    
     pub fn get_vertex_offset(&self, usage: Usage) -> u8 {
         self.vao.get_vertex_offset(usage)
     }
@@ -618,38 +994,595 @@ impl MeshRenderer {
         self.next_vertex.fill(0.0);
     }
 }
+impl Default for MeshRenderer {
+    /// Creates a renderer using the bundled `shaders::MESH_VERT`/`shaders::MESH_FRAG`
+    fn default() -> Self {
+        Self::new(shaders::MESH_VERT, shaders::MESH_FRAG)
+    }
+}
+
+
+// core in GL 4.6, but the bindings are generated for 4.5
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MipmapMode {
+    /// Mipmaps are not generated or sampled
+    None,
+    /// Samples the nearest mipmap level
+    Nearest,
+    /// Blends between the two nearest mipmap levels (trilinear when combined with `Filter::Linear`)
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    ClampToBorder,
+}
+impl Wrap {
+    fn gl_enum(&self) -> GLenum {
+        match *self {
+            Wrap::Repeat => gl::REPEAT,
+            Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Wrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }
+    }
+}
+
+/// Depth comparison used when sampling depth textures with a shadow sampler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareFunc {
+    Never,
+    Less,
+    LessEqual,
+    Equal,
+    NotEqual,
+    GreaterEqual,
+    Greater,
+    Always,
+}
+impl CompareFunc {
+    fn gl_enum(&self) -> GLenum {
+        match *self {
+            CompareFunc::Never => gl::NEVER,
+            CompareFunc::Less => gl::LESS,
+            CompareFunc::LessEqual => gl::LEQUAL,
+            CompareFunc::Equal => gl::EQUAL,
+            CompareFunc::NotEqual => gl::NOTEQUAL,
+            CompareFunc::GreaterEqual => gl::GEQUAL,
+            CompareFunc::Greater => gl::GREATER,
+            CompareFunc::Always => gl::ALWAYS,
+        }
+    }
+}
+
+/// Sampling state of a texture or `Sampler`. The default matches the old hardcoded behavior: nearest
+/// filtering, no mipmaps, repeat wrapping.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureParams {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub mipmap: MipmapMode,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub wrap_r: Wrap,
+    pub border_color: [f32; 4],
+    /// Maximum anisotropy, clamped to what the driver supports. 1.0 disables anisotropic filtering.
+    pub anisotropy: f32,
+    pub lod_bias: f32,
+    pub compare: Option<CompareFunc>,
+}
+impl Default for TextureParams {
+    fn default() -> Self {
+        Self {
+            min_filter: Filter::Nearest,
+            mag_filter: Filter::Nearest,
+            mipmap: MipmapMode::None,
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            wrap_r: Wrap::Repeat,
+            border_color: [0.0, 0.0, 0.0, 0.0],
+            anisotropy: 1.0,
+            lod_bias: 0.0,
+            compare: None,
+        }
+    }
+}
+impl TextureParams {
+    /// Linear min/mag filtering without mipmaps
+    pub fn linear() -> Self {
+        Self::default().with_filter(Filter::Linear, Filter::Linear)
+    }
+    
+    /// Linear filtering with trilinear mipmapping
+    pub fn trilinear() -> Self {
+        Self::linear().with_mipmaps(MipmapMode::Linear)
+    }
+    
+    pub fn with_filter(mut self, min: Filter, mag: Filter) -> Self {
+        self.min_filter = min;
+        self.mag_filter = mag;
+        
+        self
+    }
+    
+    pub fn with_mipmaps(mut self, mipmap: MipmapMode) -> Self {
+        self.mipmap = mipmap;
+        
+        self
+    }
+    
+    /// Sets the wrap mode for all three axes
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap_s = wrap;
+        self.wrap_t = wrap;
+        self.wrap_r = wrap;
+        
+        self
+    }
+    
+    pub fn with_border_color(mut self, r: f32, g: f32, b: f32, a: f32) -> Self {
+        self.border_color = [r, g, b, a];
+        
+        self
+    }
+    
+    pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy;
+        
+        self
+    }
+    
+    pub fn with_lod_bias(mut self, lod_bias: f32) -> Self {
+        self.lod_bias = lod_bias;
+        
+        self
+    }
+    
+    pub fn with_compare(mut self, compare: CompareFunc) -> Self {
+        self.compare = Some(compare);
+        
+        self
+    }
+    
+    fn min_filter_enum(&self) -> GLenum {
+        match (self.min_filter, self.mipmap) {
+            (Filter::Nearest, MipmapMode::None) => gl::NEAREST,
+            (Filter::Linear, MipmapMode::None) => gl::LINEAR,
+            (Filter::Nearest, MipmapMode::Nearest) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, MipmapMode::Nearest) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::Nearest, MipmapMode::Linear) => gl::NEAREST_MIPMAP_LINEAR,
+            (Filter::Linear, MipmapMode::Linear) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+    
+    /// Applies these parameters to the texture currently bound to `target`
+    pub(crate) fn apply(&self, target: GLenum) {
+        unsafe {
+            self.set(
+                |pname, val| gl::TexParameteri(target, pname, val),
+                |pname, val| gl::TexParameterf(target, pname, val),
+                |pname, val| gl::TexParameterfv(target, pname, val),
+            );
+        }
+    }
+    
+    fn apply_sampler(&self, sampler: GLuint) {
+        unsafe {
+            self.set(
+                |pname, val| gl::SamplerParameteri(sampler, pname, val),
+                |pname, val| gl::SamplerParameterf(sampler, pname, val),
+                |pname, val| gl::SamplerParameterfv(sampler, pname, val),
+            );
+        }
+    }
+    
+    unsafe fn set(&self, seti: impl Fn(GLenum, GLint), setf: impl Fn(GLenum, GLfloat), setfv: impl Fn(GLenum, *const GLfloat)) {
+        let mag = match self.mag_filter {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        };
+        seti(gl::TEXTURE_MIN_FILTER, self.min_filter_enum() as GLint);
+        seti(gl::TEXTURE_MAG_FILTER, mag as GLint);
+        seti(gl::TEXTURE_WRAP_S, self.wrap_s.gl_enum() as GLint);
+        seti(gl::TEXTURE_WRAP_T, self.wrap_t.gl_enum() as GLint);
+        seti(gl::TEXTURE_WRAP_R, self.wrap_r.gl_enum() as GLint);
+        setfv(gl::TEXTURE_BORDER_COLOR, self.border_color.as_ptr());
+        setf(gl::TEXTURE_LOD_BIAS, self.lod_bias);
+        
+        let mut max_anisotropy = 1.0;
+        gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy);
+        setf(TEXTURE_MAX_ANISOTROPY, self.anisotropy.clamp(1.0, max_anisotropy.max(1.0)));
+        
+        match self.compare {
+            Some(func) => {
+                seti(gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as GLint);
+                seti(gl::TEXTURE_COMPARE_FUNC, func.gl_enum() as GLint);
+            },
+            None => seti(gl::TEXTURE_COMPARE_MODE, gl::NONE as GLint),
+        }
+    }
+}
+
+/// Sampling state which can be shared by many textures. While bound to a texture unit it overrides the
+/// `TextureParams` of whatever texture is bound to that unit.
+#[derive(Debug)]
+pub struct Sampler {
+    id: GLuint,
+    params: TextureParams,
+}
+impl Sampler {
+    pub fn new(params: TextureParams) -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenSamplers(1, &mut id);
+        }
+        params.apply_sampler(id);
+        
+        Self {
+            id,
+            params,
+        }
+    }
+    
+    pub fn set_params(&mut self, params: TextureParams) {
+        params.apply_sampler(self.id);
+        self.params = params;
+    }
+    
+    pub fn params(&self) -> &TextureParams {
+        &self.params
+    }
+    
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::BindSampler(unit, self.id);
+        }
+    }
+    
+    /// Removes any sampler from `unit`, so textures bound there use their own parameters again.
+    pub fn unbind(unit: u32) {
+        unsafe {
+            gl::BindSampler(unit, 0);
+        }
+    }
+}
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSamplers(1, &self.id);
+        }
+    }
+}
+
+/// Storage format of a texture on the GPU, and the layout of pixel data uploaded to it.
+///
+/// Integer formats (`*Ui`/`*I`) must be sampled with `usampler`/`isampler` and `Filter::Nearest`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    R8,
+    Rg8,
+    Rgb8,
+    Rgba8,
+    Srgb8,
+    Srgb8Alpha8,
+    R16,
+    Rg16,
+    Rgb16,
+    Rgba16,
+    R16F,
+    Rg16F,
+    Rgb16F,
+    Rgba16F,
+    R32F,
+    Rg32F,
+    Rgb32F,
+    Rgba32F,
+    R8Ui,
+    Rgba8Ui,
+    R16Ui,
+    R32Ui,
+    R32I,
+    Rgba32Ui,
+    Depth16,
+    Depth24,
+    Depth32F,
+    Depth24Stencil8,
+    Depth32FStencil8,
+}
+impl TextureFormat {
+    pub fn internal_format(&self) -> GLenum {
+        match *self {
+            TextureFormat::R8 => gl::R8,
+            TextureFormat::Rg8 => gl::RG8,
+            TextureFormat::Rgb8 => gl::RGB8,
+            TextureFormat::Rgba8 => gl::RGBA8,
+            TextureFormat::Srgb8 => gl::SRGB8,
+            TextureFormat::Srgb8Alpha8 => gl::SRGB8_ALPHA8,
+            TextureFormat::R16 => gl::R16,
+            TextureFormat::Rg16 => gl::RG16,
+            TextureFormat::Rgb16 => gl::RGB16,
+            TextureFormat::Rgba16 => gl::RGBA16,
+            TextureFormat::R16F => gl::R16F,
+            TextureFormat::Rg16F => gl::RG16F,
+            TextureFormat::Rgb16F => gl::RGB16F,
+            TextureFormat::Rgba16F => gl::RGBA16F,
+            TextureFormat::R32F => gl::R32F,
+            TextureFormat::Rg32F => gl::RG32F,
+            TextureFormat::Rgb32F => gl::RGB32F,
+            TextureFormat::Rgba32F => gl::RGBA32F,
+            TextureFormat::R8Ui => gl::R8UI,
+            TextureFormat::Rgba8Ui => gl::RGBA8UI,
+            TextureFormat::R16Ui => gl::R16UI,
+            TextureFormat::R32Ui => gl::R32UI,
+            TextureFormat::R32I => gl::R32I,
+            TextureFormat::Rgba32Ui => gl::RGBA32UI,
+            TextureFormat::Depth16 => gl::DEPTH_COMPONENT16,
+            TextureFormat::Depth24 => gl::DEPTH_COMPONENT24,
+            TextureFormat::Depth32F => gl::DEPTH_COMPONENT32F,
+            TextureFormat::Depth24Stencil8 => gl::DEPTH24_STENCIL8,
+            TextureFormat::Depth32FStencil8 => gl::DEPTH32F_STENCIL8,
+        }
+    }
+    
+    /// Pixel transfer format used when uploading or reading back data
+    pub fn format(&self) -> GLenum {
+        match *self {
+            TextureFormat::R8 | TextureFormat::R16 | TextureFormat::R16F | TextureFormat::R32F => gl::RED,
+            TextureFormat::Rg8 | TextureFormat::Rg16 | TextureFormat::Rg16F | TextureFormat::Rg32F => gl::RG,
+            TextureFormat::Rgb8 | TextureFormat::Srgb8 | TextureFormat::Rgb16 | TextureFormat::Rgb16F | TextureFormat::Rgb32F => gl::RGB,
+            TextureFormat::Rgba8 | TextureFormat::Srgb8Alpha8 | TextureFormat::Rgba16 | TextureFormat::Rgba16F | TextureFormat::Rgba32F => gl::RGBA,
+            TextureFormat::R8Ui | TextureFormat::R16Ui | TextureFormat::R32Ui | TextureFormat::R32I => gl::RED_INTEGER,
+            TextureFormat::Rgba8Ui | TextureFormat::Rgba32Ui => gl::RGBA_INTEGER,
+            TextureFormat::Depth16 | TextureFormat::Depth24 | TextureFormat::Depth32F => gl::DEPTH_COMPONENT,
+            TextureFormat::Depth24Stencil8 | TextureFormat::Depth32FStencil8 => gl::DEPTH_STENCIL,
+        }
+    }
+    
+    /// Component type of pixel data. Half float formats are uploaded from, and read back as, 32-bit floats.
+    pub fn data_type(&self) -> GLenum {
+        match *self {
+            TextureFormat::R8 | TextureFormat::Rg8 | TextureFormat::Rgb8 | TextureFormat::Rgba8
+                | TextureFormat::Srgb8 | TextureFormat::Srgb8Alpha8
+                | TextureFormat::R8Ui | TextureFormat::Rgba8Ui => gl::UNSIGNED_BYTE,
+            TextureFormat::R16 | TextureFormat::Rg16 | TextureFormat::Rgb16 | TextureFormat::Rgba16
+                | TextureFormat::R16Ui | TextureFormat::Depth16 => gl::UNSIGNED_SHORT,
+            TextureFormat::R16F | TextureFormat::Rg16F | TextureFormat::Rgb16F | TextureFormat::Rgba16F
+                | TextureFormat::R32F | TextureFormat::Rg32F | TextureFormat::Rgb32F | TextureFormat::Rgba32F
+                | TextureFormat::Depth32F => gl::FLOAT,
+            TextureFormat::R32Ui | TextureFormat::Rgba32Ui | TextureFormat::Depth24 => gl::UNSIGNED_INT,
+            TextureFormat::R32I => gl::INT,
+            TextureFormat::Depth24Stencil8 => gl::UNSIGNED_INT_24_8,
+            TextureFormat::Depth32FStencil8 => gl::FLOAT_32_UNSIGNED_INT_24_8_REV,
+        }
+    }
+    
+    pub fn channels(&self) -> usize {
+        match self.format() {
+            gl::RED | gl::RED_INTEGER | gl::DEPTH_COMPONENT => 1,
+            gl::RG | gl::DEPTH_STENCIL => 2,
+            gl::RGB => 3,
+            _ => 4,
+        }
+    }
+    
+    /// Size of one pixel of upload data, in bytes
+    pub fn bytes_per_pixel(&self) -> usize {
+        // packed depth/stencil formats
+        match *self {
+            TextureFormat::Depth24Stencil8 => return 4,
+            TextureFormat::Depth32FStencil8 => return 8,
+            _ => (),
+        }
+        
+        let component = match self.data_type() {
+            gl::UNSIGNED_BYTE => 1,
+            gl::UNSIGNED_SHORT => 2,
+            _ => 4,
+        };
+        
+        component * self.channels()
+    }
+    
+    /// Whether this is a depth or depth/stencil format
+    pub fn is_depth(&self) -> bool {
+        matches!(self.format(), gl::DEPTH_COMPONENT | gl::DEPTH_STENCIL)
+    }
+    
+    pub fn has_stencil(&self) -> bool {
+        self.format() == gl::DEPTH_STENCIL
+    }
+}
+
+/// Binding and sampling API shared by `Texture` and the texture types in `texture`.
+pub trait GlTexture {
+    fn id(&self) -> GLuint;
+    
+    /// Binding target, e.g. `TEXTURE_2D` or `TEXTURE_CUBE_MAP`
+    fn target(&self) -> GLenum;
+    
+    fn params(&self) -> &TextureParams;
+    
+    /// Changes the sampling parameters, generating mipmaps if they're now needed.
+    fn set_params(&mut self, params: TextureParams);
+    
+    fn bind(&self) {
+        unsafe {
+            gl::BindTexture(self.target(), self.id());
+        }
+    }
+    
+    /// Binds this texture to texture unit `unit` (i.e. `TEXTURE0 + unit`), and leaves that unit active.
+    fn bind_to(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(self.target(), self.id());
+        }
+    }
+}
 
+pub(crate) fn update_params(target: GLenum, id: GLuint, old: &TextureParams, params: &TextureParams) {
+    unsafe {
+        gl::BindTexture(target, id);
+        params.apply(target);
+        if params.mipmap != MipmapMode::None && old.mipmap == MipmapMode::None {
+            gl::GenerateMipmap(target);
+        }
+        gl::BindTexture(target, 0);
+    }
+}
+
+thread_local! {
+    // GL objects belong to the context's thread, so the shader is cached per thread
+    static TINT_SHADER: std::cell::RefCell<Option<ShaderProgram>> = const { std::cell::RefCell::new(None) };
+}
 
 #[derive(Debug)]
 pub struct Texture {
     id: u32,
     pub width: u32,
     pub height: u32,
-    original_image: RgbaImage,
+    format: TextureFormat,
+    params: TextureParams,
+    original_image: Option<RgbaImage>,
+    compressed: Option<CompressedFormat>,
 }
 impl Texture {
     pub fn from_path(path: &PathBuf) -> Self {
+        Self::from_path_with(path, TextureParams::default())
+    }
+    
+    pub fn from_path_with(path: &PathBuf, params: TextureParams) -> Self {
+        Self::from_path_opts(path, params, true)
+    }
+    
+    /// Loads a texture, keeping a CPU copy of the image only if `retain_image` is set. See `from_image_opts`.
+    pub fn from_path_opts(path: &PathBuf, params: TextureParams, retain_image: bool) -> Self {
         let img = image::open(path).unwrap().into_rgba8();
         
-        Self::from_image(img)
+        Self::from_image_opts(img, params, retain_image)
+    }
+    
+    /// Loads a color texture stored in sRGB, like most image files, so it's converted to linear when sampled.
+    /// Use for albedo and UI textures in a linear (HDR) pipeline, but not for data such as normal maps.
+    pub fn from_path_srgb(path: &Path, params: TextureParams) -> Self {
+        Self::from_image_srgb(image::open(path).unwrap().into_rgba8(), params)
+    }
+    
+    /// Same as `from_image_with`, stored as `Srgb8Alpha8`. See `from_path_srgb`.
+    pub fn from_image_srgb(img: RgbaImage, params: TextureParams) -> Self {
+        Self::from_image_srgb_opts(img, params, true)
+    }
+    
+    /// Same as `from_image_opts`, stored as `Srgb8Alpha8`. See `from_path_srgb`.
+    pub fn from_image_srgb_opts(img: RgbaImage, params: TextureParams, retain_image: bool) -> Self {
+        Self::from_rgba(img, TextureFormat::Srgb8Alpha8, params, retain_image)
+    }
+    
+    /// Same as `from_path`, returning an error instead of panicking if the image can't be read or decoded.
+    pub fn try_from_path(path: &Path) -> image::ImageResult<Self> {
+        Ok(Self::from_image(image::open(path)?.into_rgba8()))
+    }
+    
+    pub fn from_image(img: RgbaImage) -> Self {
+        Self::from_image_with(img, TextureParams::default())
     }
     
-    pub fn from_image(mut img: RgbaImage) -> Self {
-        img = image::imageops::flip_vertical(&img);
+    pub fn from_image_with(img: RgbaImage, params: TextureParams) -> Self {
+        Self::from_image_opts(img, params, true)
+    }
+    
+    /// Creates a texture, keeping a CPU copy of the image only if `retain_image` is set.
+    ///
+    /// The copy makes `multiply` a CPU operation; without it `multiply` renders on the GPU instead.
+    pub fn from_image_opts(img: RgbaImage, params: TextureParams, retain_image: bool) -> Self {
+        Self::from_rgba(img, TextureFormat::Rgba8, params, retain_image)
+    }
+    
+    fn from_rgba(img: RgbaImage, format: TextureFormat, params: TextureParams, retain_image: bool) -> Self {
+        let img = image::imageops::flip_vertical(&img);
         
-        let width = img.width();
-        let height = img.height();
-        let original_image = img.clone();
+        let mut tex = Self::gl_gen(img.width(), img.height(), format, img.as_raw().as_ptr() as *const c_void, params);
+        if retain_image {
+            tex.original_image = Some(img);
+        }
         
-        Self {
-            id: Self::gl_gen(img),
-            width,
-            height,
-            original_image,
+        tex
+    }
+    
+    /// Creates a texture which keeps the image's channel count and bit depth, e.g. `R8` for grayscale
+    /// images, `Rgba16` for 16-bit PNGs or `Rgba32F` for OpenEXR images. Grayscale textures are
+    /// swizzled to sample as (L, L, L, 1), or (L, L, L, A) with alpha, like the RGBA image they stand for.
+    pub fn from_dynamic_image(img: DynamicImage, params: TextureParams) -> Self {
+        let (width, height) = img.dimensions();
+        let swizzle = match img {
+            DynamicImage::ImageLuma8(_) | DynamicImage::ImageLuma16(_) => Some([gl::RED, gl::RED, gl::RED, gl::ONE]),
+            DynamicImage::ImageLumaA8(_) | DynamicImage::ImageLumaA16(_) => Some([gl::RED, gl::RED, gl::RED, gl::GREEN]),
+            _ => None,
+        };
+        let (format, bytes) = match img {
+            DynamicImage::ImageRgba8(img) => return Self::from_image_with(img, params),
+            DynamicImage::ImageLuma8(img) => (TextureFormat::R8, img.into_raw()),
+            DynamicImage::ImageLumaA8(img) => (TextureFormat::Rg8, img.into_raw()),
+            DynamicImage::ImageRgb8(img) => (TextureFormat::Rgb8, img.into_raw()),
+            DynamicImage::ImageLuma16(img) => (TextureFormat::R16, ne_bytes(img.as_raw(), u16::to_ne_bytes)),
+            DynamicImage::ImageLumaA16(img) => (TextureFormat::Rg16, ne_bytes(img.as_raw(), u16::to_ne_bytes)),
+            DynamicImage::ImageRgb16(img) => (TextureFormat::Rgb16, ne_bytes(img.as_raw(), u16::to_ne_bytes)),
+            DynamicImage::ImageRgba16(img) => (TextureFormat::Rgba16, ne_bytes(img.as_raw(), u16::to_ne_bytes)),
+            DynamicImage::ImageRgb32F(img) => (TextureFormat::Rgb32F, ne_bytes(img.as_raw(), f32::to_ne_bytes)),
+            DynamicImage::ImageRgba32F(img) => (TextureFormat::Rgba32F, ne_bytes(img.as_raw(), f32::to_ne_bytes)),
+            _ => return Self::from_image_with(img.into_rgba8(), params),
+        };
+        
+        let tex = Self::from_bytes(width, height, format, &bytes, params);
+        if let Some(swizzle) = swizzle {
+            unsafe {
+                gl::TextureParameteriv(tex.id, gl::TEXTURE_SWIZZLE_RGBA, swizzle.map(|channel| channel as GLint).as_ptr());
+            }
         }
+        
+        tex
+    }
+    
+    /// Creates a texture from raw pixel data laid out as described by `format`, with rows ordered top to
+    /// bottom like an image file.
+    pub fn from_bytes(width: u32, height: u32, format: TextureFormat, data: &[u8], params: TextureParams) -> Self {
+        let row = width as usize * format.bytes_per_pixel();
+        if data.len() != row * height as usize {
+            panic!("Expected {} bytes for a {}x{} {:?} texture, got {}", row * height as usize, width, height, format, data.len());
+        }
+        
+        let flipped: Vec<u8> = data.chunks_exact(row).rev().flatten().copied().collect();
+        
+        Self::gl_gen(width, height, format, flipped.as_ptr() as *const c_void, params)
+    }
+    
+    /// Same as `from_bytes`, for float formats (including half float formats, which are converted by the driver).
+    pub fn from_floats(width: u32, height: u32, format: TextureFormat, data: &[f32], params: TextureParams) -> Self {
+        if format.data_type() != gl::FLOAT {
+            panic!("Texture format {:?} is not a float format", format);
+        }
+        
+        Self::from_bytes(width, height, format, &ne_bytes(data, f32::to_ne_bytes), params)
+    }
+    
+    /// Creates a texture with undefined contents, e.g. to render into with a `Framebuffer`.
+    pub fn empty(width: u32, height: u32, format: TextureFormat, params: TextureParams) -> Self {
+        Self::gl_gen(width, height, format, std::ptr::null(), params)
     }
     
-    fn gl_gen(img: RgbaImage) -> u32 {
+    fn gl_gen(width: u32, height: u32, format: TextureFormat, data: *const c_void, params: TextureParams) -> Self {
         let mut id = 0;
         
         unsafe {
@@ -657,16 +1590,66 @@ impl Texture {
             
             gl::BindTexture(gl::TEXTURE_2D, id);
             gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            params.apply(gl::TEXTURE_2D);
             
-            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as GLint, img.width() as GLsizei, img.height() as GLsizei, 0, gl::RGBA, gl::UNSIGNED_BYTE, img.into_raw().as_ptr() as *const c_void);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, format.internal_format() as GLint, width as GLsizei, height as GLsizei, 0, format.format(), format.data_type(), data);
             
-            gl::GenerateMipmap(gl::TEXTURE_2D);
+            if params.mipmap != MipmapMode::None {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
             gl::BindTexture(gl::TEXTURE_2D, 0);
         }
         
-        id
+        Self {
+            id,
+            width,
+            height,
+            format,
+            params,
+            original_image: None,
+            compressed: None,
+        }
+    }
+    
+    /// Wraps a texture object created elsewhere in the crate, e.g. by the compressed texture loaders.
+    /// `compressed` is the block format of the GPU storage, if it wasn't decoded on upload.
+    pub(crate) fn from_raw(id: GLuint, width: u32, height: u32, format: TextureFormat, params: TextureParams, compressed: Option<CompressedFormat>) -> Self {
+        Self {
+            id,
+            width,
+            height,
+            format,
+            params,
+            original_image: None,
+            compressed,
+        }
+    }
+    
+    /// Format used for uploads and readback. For compressed textures this is the format they decode to,
+    /// `Rgba8` or `Srgb8Alpha8`; see `compressed_format`.
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+    
+    /// The block compression the texture is stored with on the GPU, if any. Compressed textures can't be
+    /// updated or have mipmaps generated.
+    pub fn compressed_format(&self) -> Option<CompressedFormat> {
+        self.compressed
+    }
+    
+    /// Changes the sampling parameters, generating mipmaps if they're now needed.
+    pub fn set_params(&mut self, params: TextureParams) {
+        if let Some(compressed) = self.compressed {
+            if params.mipmap != MipmapMode::None && self.params.mipmap == MipmapMode::None {
+                panic!("Can't generate mipmaps for a {:?} compressed texture, store them in the file instead", compressed);
+            }
+        }
+        update_params(gl::TEXTURE_2D, self.id, &self.params, &params);
+        self.params = params;
+    }
+    
+    pub fn params(&self) -> &TextureParams {
+        &self.params
     }
     
     pub fn bind(&self) {
@@ -675,9 +1658,110 @@ impl Texture {
         }
     }
     
+    /// Binds this texture to texture unit `unit` (i.e. `TEXTURE0 + unit`), and leaves that unit active.
+    pub fn bind_to(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+        }
+    }
+    
+    /// Replaces the pixels at `x`, `y` (from the top-left, like the source image) with `img`.
+    ///
+    /// The texture must be `Rgba8` or `Srgb8Alpha8`. Any retained CPU copy of the image is updated too.
+    pub fn update_region(&mut self, x: u32, y: u32, img: &RgbaImage) {
+        if self.format != TextureFormat::Rgba8 && self.format != TextureFormat::Srgb8Alpha8 {
+            panic!("Texture::update_region requires an 8-bit RGBA texture, this one is {:?}", self.format);
+        }
+        
+        self.update_region_bytes(x, y, img.width(), img.height(), img.as_raw());
+    }
+    
+    /// Replaces a region with raw pixel data in this texture's `format()`, with rows ordered top to bottom.
+    pub fn update_region_bytes(&mut self, x: u32, y: u32, width: u32, height: u32, data: &[u8]) {
+        if let Some(compressed) = self.compressed {
+            panic!("Texture::update_region can't update a {:?} compressed texture", compressed);
+        }
+        if x + width > self.width || y + height > self.height {
+            panic!("Region {}x{} at ({}, {}) is outside of the {}x{} texture", width, height, x, y, self.width, self.height);
+        }
+        let row = width as usize * self.format.bytes_per_pixel();
+        if data.len() != row * height as usize {
+            panic!("Expected {} bytes for a {}x{} {:?} region, got {}", row * height as usize, width, height, self.format, data.len());
+        }
+        
+        // textures are stored bottom row first
+        let gl_y = self.height - y - height;
+        let flipped: Vec<u8> = data.chunks_exact(row).rev().flatten().copied().collect();
+        
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(gl::TEXTURE_2D, 0, x as GLint, gl_y as GLint, width as GLsizei, height as GLsizei, self.format.format(), self.format.data_type(), flipped.as_ptr() as *const c_void);
+            if self.params.mipmap != MipmapMode::None {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        
+        if let Some(original) = &mut self.original_image {
+            let region = RgbaImage::from_raw(width, height, flipped).unwrap();
+            image::imageops::replace(original, &region, x as i64, gl_y as i64);
+        }
+    }
+    
+    /// Reads the texture back from the GPU, converted to RGBA8 and in the same orientation as the source image.
+    pub fn read_back(&self) -> RgbaImage {
+        if matches!(self.format.format(), gl::RED_INTEGER | gl::RGBA_INTEGER) || self.format.is_depth() {
+            panic!("Texture format {:?} can't be read back as RGBA8, use read_back_bytes()", self.format);
+        }
+        
+        let data = self.get_image(gl::RGBA, gl::UNSIGNED_BYTE, 4);
+        let img = RgbaImage::from_raw(self.width, self.height, data).unwrap();
+        
+        image::imageops::flip_vertical(&img)
+    }
+    
+    /// Reads the texture back from the GPU in its own `format()`, with rows ordered top to bottom.
+    pub fn read_back_bytes(&self) -> Vec<u8> {
+        let bpp = self.format.bytes_per_pixel();
+        let data = self.get_image(self.format.format(), self.format.data_type(), bpp);
+        
+        data.chunks_exact(self.width as usize * bpp).rev().flatten().copied().collect()
+    }
+    
+    fn get_image(&self, format: GLenum, data_type: GLenum, bytes_per_pixel: usize) -> Vec<u8> {
+        let mut data = vec![0u8; self.width as usize * self.height as usize * bytes_per_pixel];
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::GetTexImage(gl::TEXTURE_2D, 0, format, data_type, data.as_mut_ptr() as *mut c_void);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        
+        data
+    }
+    
+    /// Whether a CPU copy of the image is kept
+    pub fn has_image(&self) -> bool {
+        self.original_image.is_some()
+    }
+    
+    /// Frees the CPU copy of the image, if one is kept.
+    pub fn discard_image(&mut self) {
+        self.original_image = None;
+    }
+    
     /// Returns a clone of this image, with every pixel multiplied by the provided color
+    ///
+    /// Uses the CPU copy of the image if one is kept, otherwise the texture is rendered into the new one
+    /// with a tint shader. Swizzled grayscale textures (see `from_dynamic_image`) come back as RGBA. To tint
+    /// while drawing instead, pass the color to `TextureRenderer::texture`.
     pub fn multiply(&self, r: f32, g: f32, b: f32, a: f32) -> Self {
-        let mut img = self.original_image.clone();
+        let mut img = match &self.original_image {
+            Some(img) => img.clone(),
+            None => return self.multiply_gpu(r, g, b, a),
+        };
         img.pixels_mut().for_each(|pixel| {
             pixel.0[0] = ((pixel.0[0] as f32) * r) as u8;
             pixel.0[1] = ((pixel.0[1] as f32) * g) as u8;
@@ -685,7 +1769,125 @@ impl Texture {
             pixel.0[3] = ((pixel.0[3] as f32) * a) as u8;
         });
         
-        Self::from_image(img)
+        // the copy is already flipped, so it's uploaded directly rather than through from_image
+        let mut tex = Self::gl_gen(img.width(), img.height(), self.format, img.as_raw().as_ptr() as *const c_void, self.params);
+        tex.original_image = Some(img);
+        
+        tex
+    }
+    
+    fn multiply_gpu(&self, r: f32, g: f32, b: f32, a: f32) -> Self {
+        if matches!(self.format.format(), gl::RED_INTEGER | gl::RGBA_INTEGER) || self.format.is_depth() {
+            panic!("Texture::multiply doesn't support format {:?}", self.format);
+        }
+        
+        // swizzled grayscale textures are tinted into all four channels, so colored tints survive
+        let mut swizzle = [0; 4];
+        unsafe {
+            gl::GetTextureParameteriv(self.id, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_mut_ptr());
+        }
+        let identity = [gl::RED, gl::GREEN, gl::BLUE, gl::ALPHA].map(|channel| channel as GLint);
+        let format = match self.format {
+            TextureFormat::R8 | TextureFormat::Rg8 if swizzle != identity => TextureFormat::Rgba8,
+            TextureFormat::R16 | TextureFormat::Rg16 if swizzle != identity => TextureFormat::Rgba16,
+            format => format,
+        };
+        let tex = Self::empty(self.width, self.height, format, self.params);
+        
+        TINT_SHADER.with(|shader| {
+            let mut shader = shader.borrow_mut();
+            let shader = shader.get_or_insert_with(|| {
+                let mut shader = ShaderProgram::new();
+                shader.create_vertex_shader(shaders::FULLSCREEN_VERT);
+                shader.create_fragment_shader(shaders::TINT_FRAG);
+                shader.link();
+                
+                shader
+            });
+            
+            unsafe {
+                let mut viewport = [0; 4];
+                let mut previous = 0;
+                gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+                gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut previous);
+                let depth_test = gl::IsEnabled(gl::DEPTH_TEST);
+                let blend = gl::IsEnabled(gl::BLEND);
+                let framebuffer_srgb = gl::IsEnabled(gl::FRAMEBUFFER_SRGB);
+                
+                let (mut fbo, mut vao) = (0, 0);
+                gl::GenFramebuffers(1, &mut fbo);
+                gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+                gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, tex.id, 0);
+                let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+                if status != gl::FRAMEBUFFER_COMPLETE {
+                    gl::BindFramebuffer(gl::FRAMEBUFFER, previous as GLuint);
+                    gl::DeleteFramebuffers(1, &fbo);
+                    panic!("Texture::multiply can't render to format {:?} (framebuffer status 0x{:X}), keep the image on the CPU to tint it", format, status);
+                }
+                gl::GenVertexArrays(1, &mut vao);
+                gl::BindVertexArray(vao);
+                
+                gl::Viewport(0, 0, self.width as GLsizei, self.height as GLsizei);
+                gl::Disable(gl::DEPTH_TEST);
+                gl::Disable(gl::BLEND);
+                // sRGB sources are sampled as linear, so they must be encoded again on write
+                if matches!(format, TextureFormat::Srgb8 | TextureFormat::Srgb8Alpha8) {
+                    gl::Enable(gl::FRAMEBUFFER_SRGB);
+                } else {
+                    gl::Disable(gl::FRAMEBUFFER_SRGB);
+                }
+                
+                shader.bind();
+                self.bind_to(0);
+                shader.set_uniform1i32(UNIFORM_TEXTURE_SAMPLER, 0);
+                shader.set_uniform4f32("tint", r, g, b, a);
+                gl::DrawArrays(gl::TRIANGLES, 0, 3);
+                shader.unbind();
+                
+                gl::BindVertexArray(0);
+                gl::DeleteVertexArrays(1, &vao);
+                gl::BindFramebuffer(gl::FRAMEBUFFER, previous as GLuint);
+                gl::DeleteFramebuffers(1, &fbo);
+                
+                gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+                if depth_test == gl::TRUE {
+                    gl::Enable(gl::DEPTH_TEST);
+                }
+                if blend == gl::TRUE {
+                    gl::Enable(gl::BLEND);
+                }
+                if framebuffer_srgb == gl::TRUE {
+                    gl::Enable(gl::FRAMEBUFFER_SRGB);
+                } else {
+                    gl::Disable(gl::FRAMEBUFFER_SRGB);
+                }
+                
+                if tex.params.mipmap != MipmapMode::None {
+                    gl::BindTexture(gl::TEXTURE_2D, tex.id);
+                    gl::GenerateMipmap(gl::TEXTURE_2D);
+                    gl::BindTexture(gl::TEXTURE_2D, 0);
+                }
+            }
+        });
+        
+        tex
+    }
+}
+impl GlTexture for Texture {
+    fn id(&self) -> GLuint {
+        self.id
+    }
+    
+    fn target(&self) -> GLenum {
+        gl::TEXTURE_2D
+    }
+    
+    fn params(&self) -> &TextureParams {
+        &self.params
+    }
+    
+    fn set_params(&mut self, params: TextureParams) {
+        Texture::set_params(self, params);
     }
 }
 impl Drop for Texture {
@@ -739,7 +1941,7 @@ impl<'a> TextureRenderer<'a> {
         
         self.last_tex.unwrap().bind();
         
-        self.shader.set_uniform1i32("textureSampler", 0);
+        self.shader.set_uniform1i32(UNIFORM_TEXTURE_SAMPLER, 0);
         self.mesh.render(&self.shader, false, gl::TRIANGLES, self.combined.unwrap().clone());
         self.mesh.clear();
         self.dirty = false;
@@ -766,6 +1968,22 @@ impl<'a> TextureRenderer<'a> {
         self.texture(tex, x, y, width, height, u, v, u2, v2, 1.0, 1.0, 1.0, 1.0);
     }
     
+    /// Draws `region` of `tex` at its pixel size.
+    pub fn region(&mut self, tex: &'a Texture, region: &TextureRegion, x: f32, y: f32) {
+        self.region_sized(tex, region, x, y, region.width as f32, region.height as f32);
+    }
+    
+    pub fn region_sized(&mut self, tex: &'a Texture, region: &TextureRegion, x: f32, y: f32, width: f32, height: f32) {
+        self.region_tinted(tex, region, [x, y, width, height], [1.0; 4]);
+    }
+    
+    /// Draws `region` of `tex` into `dest`, given as `[x, y, width, height]`, multiplied by `tint`, an RGBA color.
+    pub fn region_tinted(&mut self, tex: &'a Texture, region: &TextureRegion, dest: [f32; 4], tint: [f32; 4]) {
+        let [x, y, width, height] = dest;
+        let [r, g, b, a] = tint;
+        self.texture(tex, x, y, width, height, region.u, region.v, region.u2, region.v2, r, g, b, a);
+    }
+    
     pub fn texture(&mut self, tex: &'a Texture, x: f32, y: f32, width: f32, height: f32, u: f32, v: f32, u2: f32, v2: f32, r: f32, g: f32, b: f32, a: f32) {
         if self.last_tex.is_none() {
             self.last_tex = Some(tex);
@@ -830,4 +2048,10 @@ impl<'a> TextureRenderer<'a> {
         
         self.next_vertex.fill(0.0);
     }
+}
+impl<'a> Default for TextureRenderer<'a> {
+    /// Creates a renderer using the bundled `shaders::TEXTURE_VERT`/`shaders::TEXTURE_FRAG`
+    fn default() -> Self {
+        Self::new(shaders::TEXTURE_VERT, shaders::TEXTURE_FRAG)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,418 @@
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use gl::types::*;
+use image::ImageError;
+use crate::graphics::{GlTexture, ShaderProgram, Texture, TextureFormat, TextureParams, Wrap};
+use crate::shaders;
+use crate::texture::TextureCube;
+
+const CACHE_MAGIC: &[u8; 8] = b"IBLCACHE";
+const CACHE_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum IblError {
+    Io(PathBuf, std::io::Error),
+    Decode(PathBuf, ImageError),
+    /// The file is neither a Radiance HDR nor an OpenEXR image
+    Unsupported(PathBuf),
+    /// A cache file is truncated or was written by another version
+    InvalidCache(PathBuf),
+}
+impl Display for IblError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IblError::Io(path, err) => write!(f, "Error reading or writing {}: {}", path.display(), err),
+            IblError::Decode(path, err) => write!(f, "Error decoding {}: {}", path.display(), err),
+            IblError::Unsupported(path) => write!(f, "Unsupported environment map format: {} (expected Radiance .hdr or OpenEXR .exr)", path.display()),
+            IblError::InvalidCache(path) => write!(f, "Invalid IBL cache file: {}", path.display()),
+        }
+    }
+}
+impl std::error::Error for IblError {}
+
+/// Sizes and quality of the maps computed by `Environment`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IblSettings {
+    /// Face size of the environment cube map
+    pub environment_size: u32,
+    /// Face size of the diffuse irradiance map, which has no fine detail
+    pub irradiance_size: u32,
+    /// Face size of the sharpest level of the prefiltered specular map
+    pub prefiltered_size: u32,
+    /// Mip levels of the prefiltered map, from roughness 0 to 1
+    pub prefiltered_levels: u32,
+    /// Size of the BRDF lookup table
+    pub brdf_size: u32,
+    /// Importance samples per texel for the prefiltered map and BRDF lookup table
+    pub samples: u32,
+}
+impl Default for IblSettings {
+    fn default() -> Self {
+        Self {
+            environment_size: 512,
+            irradiance_size: 32,
+            prefiltered_size: 128,
+            prefiltered_levels: 5,
+            brdf_size: 256,
+            samples: 1024,
+        }
+    }
+}
+
+/// Loads a Radiance HDR (.hdr) or OpenEXR (.exr) image as an `Rgb32F` texture with linear filtering, wrapping
+/// horizontally as an equirectangular panorama. Alpha in EXR files is ignored.
+pub fn load_hdr(path: &Path) -> Result<Texture, IblError> {
+    let supported = path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| ext.eq_ignore_ascii_case("hdr") || ext.eq_ignore_ascii_case("exr"));
+    if !supported {
+        return Err(IblError::Unsupported(path.to_owned()));
+    }
+    
+    let img = image::open(path).map_err(|err| match err {
+        ImageError::IoError(err) => IblError::Io(path.to_owned(), err),
+        err => IblError::Decode(path.to_owned(), err),
+    })?.into_rgb32f();
+    
+    let mut params = TextureParams::linear();
+    params.wrap_t = Wrap::ClampToEdge;
+    
+    Ok(Texture::from_floats(img.width(), img.height(), TextureFormat::Rgb32F, img.as_raw(), params))
+}
+
+/// Image based lighting precomputed from an HDR environment: the environment as a cube map (e.g. for a skybox),
+/// a diffuse irradiance map, a specular map prefiltered per roughness level, and the split sum BRDF lookup table.
+///
+/// ```ignore
+/// let environment = Environment::load_cached(Path::new("sky.hdr"), Path::new("sky.ibl"), IblSettings::default())?;
+/// let material = lit_material(ShadingModel::Pbr, mesh.attributes(), &["IBL"]);
+/// lights.environment = Some(Rc::new(environment));
+/// ```
+///
+/// The maps are computed on the GPU, which takes a noticeable moment at the default settings, so `load_cached`
+/// stores them in a cache file to skip the work on later runs.
+pub struct Environment {
+    environment: TextureCube,
+    irradiance: TextureCube,
+    prefiltered: TextureCube,
+    brdf_lut: Texture,
+    settings: IblSettings,
+}
+impl Environment {
+    /// Computes the maps from an HDR or EXR equirectangular panorama with the default settings.
+    pub fn from_hdr(path: &Path) -> Result<Self, IblError> {
+        Self::from_hdr_with(path, IblSettings::default())
+    }
+    
+    pub fn from_hdr_with(path: &Path, settings: IblSettings) -> Result<Self, IblError> {
+        Ok(Self::from_panorama(&load_hdr(path)?, settings))
+    }
+    
+    /// Computes the maps from an equirectangular panorama texture, see `load_hdr`.
+    pub fn from_panorama(panorama: &Texture, settings: IblSettings) -> Self {
+        let environment = Self::from_settings(settings);
+        let baker = Baker::new();
+        
+        let shader = Baker::shader(shaders::IBL_EQUIRECT_FRAG);
+        shader.bind();
+        panorama.bind_to(0);
+        shader.set_uniform1i32("panorama", 0);
+        baker.draw_cube(&shader, &environment.environment, 0);
+        unsafe {
+            gl::GenerateTextureMipmap(environment.environment.id());
+        }
+        
+        environment.environment.bind_to(0);
+        
+        let shader = Baker::shader(shaders::IBL_IRRADIANCE_FRAG);
+        shader.bind();
+        shader.set_uniform1i32("environment", 0);
+        baker.draw_cube(&shader, &environment.irradiance, 0);
+        
+        let shader = Baker::shader(shaders::IBL_PREFILTER_FRAG);
+        shader.bind();
+        shader.set_uniform1i32("environment", 0);
+        shader.set_uniform1i32("sampleCount", settings.samples as i32);
+        shader.set_uniform1f32("environmentSize", settings.environment_size as f32);
+        let levels = environment.prefiltered_levels();
+        for level in 0..levels {
+            shader.set_uniform1f32("roughness", level as f32 / (levels - 1).max(1) as f32);
+            baker.draw_cube(&shader, &environment.prefiltered, level);
+        }
+        
+        let shader = Baker::shader(shaders::IBL_BRDF_FRAG);
+        shader.bind();
+        shader.set_uniform1i32("sampleCount", settings.samples as i32);
+        baker.draw_2d(&environment.brdf_lut);
+        shader.unbind();
+        
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        
+        environment
+    }
+    
+    /// Loads the maps from `cache_path` if it's up to date with `hdr_path` and was made with `settings`, and
+    /// otherwise computes them from `hdr_path` and writes the cache.
+    pub fn load_cached(hdr_path: &Path, cache_path: &Path, settings: IblSettings) -> Result<Self, IblError> {
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        let up_to_date = match (modified(hdr_path), modified(cache_path)) {
+            (Some(hdr), Some(cache)) => cache >= hdr,
+            (None, Some(_)) => true,
+            _ => false,
+        };
+        
+        if up_to_date {
+            if let Ok(environment) = Self::load(cache_path) {
+                if environment.settings == settings {
+                    return Ok(environment);
+                }
+            }
+        }
+        
+        let environment = Self::from_hdr_with(hdr_path, settings)?;
+        environment.save(cache_path)?;
+        
+        Ok(environment)
+    }
+    
+    /// Writes the maps to a cache file, as half floats.
+    pub fn save(&self, path: &Path) -> Result<(), IblError> {
+        let io_err = |err| IblError::Io(path.to_owned(), err);
+        let mut writer = BufWriter::new(File::create(path).map_err(io_err)?);
+        
+        let settings = &self.settings;
+        let mut header = CACHE_MAGIC.to_vec();
+        for value in [CACHE_VERSION, settings.environment_size, settings.irradiance_size, settings.prefiltered_size, settings.prefiltered_levels, settings.brdf_size, settings.samples] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        writer.write_all(&header).map_err(io_err)?;
+        
+        for (texture, level, size, layers, format) in self.levels() {
+            let mut data = vec![0u8; level_len(size, layers, format)];
+            unsafe {
+                gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+                gl::GetTextureImage(texture, level as GLint, format.format(), gl::HALF_FLOAT, data.len() as GLsizei, data.as_mut_ptr() as *mut _);
+            }
+            writer.write_all(&data).map_err(io_err)?;
+        }
+        
+        writer.flush().map_err(io_err)
+    }
+    
+    /// Reads maps written by `save`.
+    pub fn load(path: &Path) -> Result<Self, IblError> {
+        let mut reader = BufReader::new(File::open(path).map_err(|err| IblError::Io(path.to_owned(), err))?);
+        let mut read = |buf: &mut [u8]| reader.read_exact(buf).map_err(|_| IblError::InvalidCache(path.to_owned()));
+        
+        let mut magic = [0u8; 8];
+        read(&mut magic)?;
+        let mut values = [0u32; 7];
+        for value in &mut values {
+            let mut bytes = [0u8; 4];
+            read(&mut bytes)?;
+            *value = u32::from_le_bytes(bytes);
+        }
+        if &magic != CACHE_MAGIC || values[0] != CACHE_VERSION || values[1..].contains(&0) || values[1..].iter().any(|&value| value > 16384) {
+            return Err(IblError::InvalidCache(path.to_owned()));
+        }
+        
+        let environment = Self::from_settings(IblSettings {
+            environment_size: values[1],
+            irradiance_size: values[2],
+            prefiltered_size: values[3],
+            prefiltered_levels: values[4],
+            brdf_size: values[5],
+            samples: values[6],
+        });
+        for (texture, level, size, layers, format) in environment.levels() {
+            let mut data = vec![0u8; level_len(size, layers, format)];
+            read(&mut data)?;
+            unsafe {
+                gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+                if layers == 1 {
+                    gl::TextureSubImage2D(texture, level as GLint, 0, 0, size as GLsizei, size as GLsizei, format.format(), gl::HALF_FLOAT, data.as_ptr() as *const _);
+                } else {
+                    gl::TextureSubImage3D(texture, level as GLint, 0, 0, 0, size as GLsizei, size as GLsizei, layers as GLsizei, format.format(), gl::HALF_FLOAT, data.as_ptr() as *const _);
+                }
+            }
+        }
+        unsafe {
+            gl::GenerateTextureMipmap(environment.environment.id());
+        }
+        
+        Ok(environment)
+    }
+    
+    /// Binds the irradiance map, prefiltered map and BRDF lookup table to units `first_unit` to `first_unit + 2`
+    /// and sets the uniforms of `shaders::IBL_GLSL`. `shader` must be bound.
+    pub fn bind(&self, shader: &ShaderProgram, first_unit: u32) {
+        self.irradiance.bind_to(first_unit);
+        shader.set_uniform1i32("irradianceMap", first_unit as i32);
+        self.prefiltered.bind_to(first_unit + 1);
+        shader.set_uniform1i32("prefilteredMap", first_unit as i32 + 1);
+        self.brdf_lut.bind_to(first_unit + 2);
+        shader.set_uniform1i32("brdfLut", first_unit as i32 + 2);
+        shader.set_uniform1f32("prefilteredLevels", self.prefiltered_levels() as f32);
+        
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+    
+    /// The environment as a mipmapped `Rgba16F` cube map
+    pub fn environment(&self) -> &TextureCube {
+        &self.environment
+    }
+    
+    /// Cosine weighted irradiance, sampled by normal
+    pub fn irradiance(&self) -> &TextureCube {
+        &self.irradiance
+    }
+    
+    /// Environment convolved with the GGX lobe, one mip level per roughness step, sampled by reflection vector
+    pub fn prefiltered(&self) -> &TextureCube {
+        &self.prefiltered
+    }
+    
+    /// `Rg16F` scale and bias applied to F0, by N.V (x) and roughness (y)
+    pub fn brdf_lut(&self) -> &Texture {
+        &self.brdf_lut
+    }
+    
+    pub fn settings(&self) -> IblSettings {
+        self.settings
+    }
+    
+    /// Creates the textures with undefined contents
+    fn from_settings(settings: IblSettings) -> Self {
+        let cube_params = TextureParams::trilinear().with_wrap(Wrap::ClampToEdge);
+        let environment = Self {
+            environment: TextureCube::empty(settings.environment_size, TextureFormat::Rgba16F, cube_params),
+            irradiance: TextureCube::empty(settings.irradiance_size, TextureFormat::Rgba16F, TextureParams::linear().with_wrap(Wrap::ClampToEdge)),
+            prefiltered: TextureCube::empty(settings.prefiltered_size, TextureFormat::Rgba16F, cube_params),
+            brdf_lut: Texture::empty(settings.brdf_size, settings.brdf_size, TextureFormat::Rg16F, TextureParams::linear().with_wrap(Wrap::ClampToEdge)),
+            settings,
+        };
+        unsafe {
+            gl::TextureParameteri(environment.prefiltered.id(), gl::TEXTURE_MAX_LEVEL, environment.prefiltered_levels() as GLint - 1);
+        }
+        
+        environment
+    }
+    
+    /// `prefiltered_levels`, limited to the mip levels `prefiltered_size` has
+    fn prefiltered_levels(&self) -> u32 {
+        let max_levels = 32 - self.settings.prefiltered_size.leading_zeros();
+        self.settings.prefiltered_levels.clamp(1, max_levels)
+    }
+    
+    /// Texture, mip level, size, layers and format of each level stored in a cache file
+    fn levels(&self) -> Vec<(GLuint, u32, u32, u32, TextureFormat)> {
+        let mut levels = vec![(self.environment.id(), 0, self.settings.environment_size, 6, TextureFormat::Rgba16F)];
+        levels.push((self.irradiance.id(), 0, self.settings.irradiance_size, 6, TextureFormat::Rgba16F));
+        for level in 0..self.prefiltered_levels() {
+            levels.push((self.prefiltered.id(), level, (self.settings.prefiltered_size >> level).max(1), 6, TextureFormat::Rgba16F));
+        }
+        levels.push((self.brdf_lut.id(), 0, self.settings.brdf_size, 1, TextureFormat::Rg16F));
+        
+        levels
+    }
+}
+
+fn level_len(size: u32, layers: u32, format: TextureFormat) -> usize {
+    let channels = if format == TextureFormat::Rg16F { 2 } else { 4 };
+    
+    (size * size * layers) as usize * channels * 2
+}
+
+/// Renders the precomputation passes with a fullscreen triangle, restoring the framebuffer, viewport and
+/// blending when dropped.
+struct Baker {
+    fbo: GLuint,
+    vao: GLuint,
+    previous: GLint,
+    viewport: [GLint; 4],
+    blend: bool,
+}
+impl Baker {
+    fn new() -> Self {
+        let mut fbo = 0;
+        let mut vao = 0;
+        let mut previous = 0;
+        let mut viewport = [0; 4];
+        let blend;
+        unsafe {
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut previous);
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            blend = gl::IsEnabled(gl::BLEND) == gl::TRUE;
+            
+            gl::CreateFramebuffers(1, &mut fbo);
+            gl::GenVertexArrays(1, &mut vao);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::Disable(gl::BLEND);
+        }
+        
+        Self {
+            fbo,
+            vao,
+            previous,
+            viewport,
+            blend,
+        }
+    }
+    
+    fn shader(fragment: &str) -> ShaderProgram {
+        let mut shader = ShaderProgram::new();
+        shader.create_vertex_shader(shaders::FULLSCREEN_VERT);
+        shader.include(shaders::IBL_COMMON_GLSL);
+        shader.create_fragment_shader(fragment);
+        shader.link();
+        
+        shader
+    }
+    
+    /// Renders every face of mip `level` of `target` with `shader`, which must be bound and have a `face` uniform
+    fn draw_cube(&self, shader: &ShaderProgram, target: &TextureCube, level: u32) {
+        let size = (target.size >> level).max(1) as GLsizei;
+        for face in 0..6 {
+            unsafe {
+                gl::NamedFramebufferTextureLayer(self.fbo, gl::COLOR_ATTACHMENT0, target.id(), level as GLint, face);
+                gl::Viewport(0, 0, size, size);
+            }
+            shader.set_uniform1i32("face", face);
+            self.draw();
+        }
+    }
+    
+    fn draw_2d(&self, target: &Texture) {
+        unsafe {
+            gl::NamedFramebufferTexture(self.fbo, gl::COLOR_ATTACHMENT0, target.id(), 0);
+            gl::Viewport(0, 0, target.width as GLsizei, target.height as GLsizei);
+        }
+        self.draw();
+    }
+    
+    fn draw(&self) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+        }
+    }
+}
+impl Drop for Baker {
+    fn drop(&mut self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.previous as GLuint);
+            gl::Viewport(self.viewport[0], self.viewport[1], self.viewport[2], self.viewport[3]);
+            if self.blend {
+                gl::Enable(gl::BLEND);
+            }
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
@@ -10,6 +10,23 @@ use sdl2::{Sdl, VideoSubsystem};
 pub mod graphics;
 pub mod camera;
 pub mod font;
+pub mod shaders;
+pub mod buffer;
+pub mod material;
+pub mod texture;
+pub mod compressed;
+pub mod sprite;
+pub mod atlas;
+pub mod animation;
+pub mod assets;
+pub mod framebuffer;
+pub mod postprocess;
+pub mod shadow;
+pub mod lighting;
+pub mod deferred;
+pub mod ibl;
+pub mod skybox;
+pub mod ssao;
 
 pub struct Screen {
     pub sdl_context: Sdl,
@@ -19,6 +36,16 @@ pub struct Screen {
 }
 impl Screen {
     pub fn new(title: &str, width: u32, height: u32, aa_buffers: Option<u8>, aa_samples: Option<u8>) -> Self {
+        Self::create(title, width, height, aa_buffers, aa_samples, false)
+    }
+    
+    /// Same as `new`, with an sRGB backbuffer: colors written to it are treated as linear and encoded to sRGB,
+    /// for gamma-correct output from a linear lighting pipeline.
+    pub fn new_srgb(title: &str, width: u32, height: u32, aa_buffers: Option<u8>, aa_samples: Option<u8>) -> Self {
+        Self::create(title, width, height, aa_buffers, aa_samples, true)
+    }
+    
+    fn create(title: &str, width: u32, height: u32, aa_buffers: Option<u8>, aa_samples: Option<u8>, srgb: bool) -> Self {
         let sdl_context = sdl2::init().unwrap();
         let video = sdl_context.video().unwrap();
         let attr = video.gl_attr();
@@ -26,6 +53,7 @@ impl Screen {
         attr.set_context_version(4, 6);
         attr.set_multisample_buffers(aa_buffers.unwrap_or(0));
         attr.set_multisample_samples(aa_samples.unwrap_or(0));
+        attr.set_framebuffer_srgb_compatible(srgb);
         
         let window = video.window(title, width, height).opengl().build().unwrap();
         let gl_context = window.gl_create_context().unwrap();
@@ -44,6 +72,10 @@ impl Screen {
                 gl::Enable(gl::MULTISAMPLE);
             }
             
+            if srgb {
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            }
+            
             gl::Viewport(0, 0, width as i32, height as i32);
         }
         
@@ -0,0 +1,311 @@
+use std::rc::Rc;
+use cgmath::{vec3, InnerSpace, Matrix4, Vector3};
+use crate::block_field;
+use crate::buffer::{BlockField, BlockType, BufferBlock, StorageBuffer};
+use crate::camera::Camera;
+use crate::graphics::{Mesh, ShaderProgram, ShaderVariants, Usage, VertexAttributes};
+use crate::ibl::Environment;
+use crate::material::{Material, UniformValue};
+use crate::shaders;
+
+/// Shader storage binding point of the `LightList` buffer, linked to the `Lights` block of `lit_material` shaders
+pub const LIGHTS_BINDING: u32 = 0;
+
+/// Light arriving from the same direction everywhere, like sunlight
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirectionalLight {
+    /// Direction the light shines in
+    pub direction: Vector3<f32>,
+    /// Linear RGB color, multiplied by `intensity`
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+}
+impl DirectionalLight {
+    pub fn new(direction: Vector3<f32>, color: Vector3<f32>, intensity: f32) -> Self {
+        Self {
+            direction: direction.normalize(),
+            color,
+            intensity,
+        }
+    }
+}
+
+/// Light shining in all directions from `position`, fading out smoothly until `range`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    pub position: Vector3<f32>,
+    /// Linear RGB color, multiplied by `intensity`
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    /// Distance at which the light reaches zero
+    pub range: f32,
+}
+impl PointLight {
+    pub fn new(position: Vector3<f32>, color: Vector3<f32>, intensity: f32, range: f32) -> Self {
+        Self {
+            position,
+            color,
+            intensity,
+            range,
+        }
+    }
+}
+
+/// A cone of light, at full intensity within `inner_angle` and fading out towards `outer_angle`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpotLight {
+    pub position: Vector3<f32>,
+    /// Direction the light shines in
+    pub direction: Vector3<f32>,
+    /// Linear RGB color, multiplied by `intensity`
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    /// Distance at which the light reaches zero
+    pub range: f32,
+    /// Full cone angle in degrees
+    pub inner_angle: f32,
+    /// Full cone angle in degrees, the light's edge
+    pub outer_angle: f32,
+}
+impl SpotLight {
+    /// A spot light of `angle` degrees, fading out over its outer 20%.
+    pub fn new(position: Vector3<f32>, direction: Vector3<f32>, color: Vector3<f32>, intensity: f32, range: f32, angle: f32) -> Self {
+        Self {
+            position,
+            direction: direction.normalize(),
+            color,
+            intensity,
+            range,
+            inner_angle: angle * 0.8,
+            outer_angle: angle,
+        }
+    }
+    
+    pub fn with_inner_angle(mut self, inner_angle: f32) -> Self {
+        self.inner_angle = inner_angle;
+        
+        self
+    }
+    
+    /// Cosines of half the inner and outer angles, as used by `spotAttenuation` in `shaders::LIGHTING_GLSL`
+    pub fn cone_cosines(&self) -> (f32, f32) {
+        let half_cos = |angle: f32| (angle.clamp(0.0, 179.0).to_radians() * 0.5).cos();
+        
+        (half_cos(self.inner_angle.min(self.outer_angle)), half_cos(self.outer_angle))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    Directional(DirectionalLight),
+    Point(PointLight),
+    Spot(SpotLight),
+}
+impl From<DirectionalLight> for Light {
+    fn from(light: DirectionalLight) -> Self {
+        Light::Directional(light)
+    }
+}
+impl From<PointLight> for Light {
+    fn from(light: PointLight) -> Self {
+        Light::Point(light)
+    }
+}
+impl From<SpotLight> for Light {
+    fn from(light: SpotLight) -> Self {
+        Light::Spot(light)
+    }
+}
+impl Light {
+    fn to_gpu(self) -> GpuLight {
+        match self {
+            Light::Directional(light) => GpuLight {
+                kind: 0,
+                direction: light.direction.normalize().into(),
+                color: (light.color * light.intensity).into(),
+                ..GpuLight::default()
+            },
+            Light::Point(light) => GpuLight {
+                position: light.position.into(),
+                kind: 1,
+                range: light.range,
+                color: (light.color * light.intensity).into(),
+                ..GpuLight::default()
+            },
+            Light::Spot(light) => {
+                let (cos_inner, cos_outer) = light.cone_cosines();
+                GpuLight {
+                    position: light.position.into(),
+                    kind: 2,
+                    direction: light.direction.normalize().into(),
+                    range: light.range,
+                    color: (light.color * light.intensity).into(),
+                    cos_inner,
+                    cos_outer,
+                    _padding: [0.0; 3],
+                }
+            },
+        }
+    }
+}
+
+/// A light as laid out in the `Lights` block of `shaders::LIGHTS_GLSL`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GpuLight {
+    pub position: [f32; 3],
+    pub kind: i32,
+    pub direction: [f32; 3],
+    pub range: f32,
+    pub color: [f32; 3],
+    pub cos_inner: f32,
+    pub cos_outer: f32,
+    _padding: [f32; 3],
+}
+unsafe impl BufferBlock for GpuLight {
+    const FIELDS: &'static [BlockField] = &[
+        block_field!(GpuLight, position, BlockType::Vec3),
+        block_field!(GpuLight, kind, BlockType::Int),
+        block_field!(GpuLight, direction, BlockType::Vec3),
+        block_field!(GpuLight, range, BlockType::Float),
+        block_field!(GpuLight, color, BlockType::Vec3),
+        block_field!(GpuLight, cos_inner, BlockType::Float),
+        block_field!(GpuLight, cos_outer, BlockType::Float),
+    ];
+}
+
+/// The lights of a scene for forward shading, uploaded to a storage buffer once per frame.
+///
+/// ```ignore
+/// let mut lights = LightList::new();
+/// lights.lights.push(PointLight::new(vec3(0.0, 2.0, 0.0), vec3(1.0, 0.9, 0.8), 10.0, 8.0).into());
+/// lights.upload();
+/// lights.render(&mut mesh, &material, &camera, model);
+/// ```
+///
+/// Works with materials from `lit_material`, or any shader including `shaders::LIGHTING_GLSL` and
+/// `shaders::LIGHTS_GLSL`. Every light is evaluated for every fragment, so keep the list short, or use
+/// `DeferredRenderer` for scenes with many lights.
+pub struct LightList {
+    pub lights: Vec<Light>,
+    /// Ambient light color, applied to every surface
+    pub ambient: Vector3<f32>,
+    /// Image based lighting for materials with the `IBL` feature, replacing `ambient`
+    pub environment: Option<Rc<Environment>>,
+    buffer: StorageBuffer<GpuLight>,
+}
+impl Default for LightList {
+    fn default() -> Self {
+        Self {
+            lights: Vec::new(),
+            ambient: vec3(0.03, 0.03, 0.03),
+            environment: None,
+            buffer: StorageBuffer::new(LIGHTS_BINDING, 16),
+        }
+    }
+}
+impl LightList {
+    /// Creates an empty list whose buffer uses shader storage binding point `LIGHTS_BINDING`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn with_ambient(mut self, r: f32, g: f32, b: f32) -> Self {
+        self.ambient = vec3(r, g, b);
+        
+        self
+    }
+    
+    /// Uploads `lights`. Call once per frame after changing them, before rendering.
+    pub fn upload(&mut self) {
+        let data: Vec<GpuLight> = self.lights.iter().map(|light| light.to_gpu()).collect();
+        self.buffer.set(&data);
+    }
+    
+    /// Links the `Lights` block of `shader` to `LIGHTS_BINDING`. Only needed once per shader, and not for
+    /// materials from `lit_material`.
+    pub fn link(&self, shader: &ShaderProgram) {
+        shader.bind_storage_block("Lights", LIGHTS_BINDING);
+    }
+    
+    /// Binds the light buffer and sets `lightCount`, `ambient` and `cameraPosition`. `shader` must be bound,
+    /// and linked to the buffer (see `link`).
+    pub fn bind(&self, shader: &ShaderProgram, camera: &Camera) {
+        self.buffer.bind();
+        shader.set_uniform1i32("lightCount", self.buffer.len() as i32);
+        shader.set_uniform3f32("ambient", self.ambient.x, self.ambient.y, self.ambient.z);
+        shader.set_uniform3f32("cameraPosition", camera.position.x, camera.position.y, camera.position.z);
+    }
+    
+    /// Renders `mesh` transformed by `model` with a lit material, as seen by `camera`. `environment` is bound to
+    /// the texture units after the material's textures.
+    pub fn render(&self, mesh: &mut Mesh, material: &Material, camera: &Camera, model: Matrix4<f32>) {
+        material.apply();
+        self.bind(&material.shader, camera);
+        if let Some(environment) = &self.environment {
+            environment.bind(&material.shader, material.textures.len() as u32);
+        }
+        material.shader.set_uniform_mat4f("model", model);
+        mesh.render(&material.shader, true, gl::TRIANGLES, camera.combined * model);
+        material.shader.unbind();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadingModel {
+    /// `shaders::BLINN_PHONG_FRAG`
+    BlinnPhong,
+    /// Metallic-roughness, `shaders::PBR_FRAG`
+    Pbr,
+}
+
+/// Defines `ShaderVariants::VERTEX_COLORS`, `NORMALS` and `TEXTURED` for the attributes of a mesh
+pub(crate) fn define_attributes(shader: &mut ShaderProgram, attribs: &VertexAttributes) {
+    let features = [(Usage::COLORS, ShaderVariants::VERTEX_COLORS), (Usage::NORMALS, ShaderVariants::NORMALS), (Usage::TEXCOORDS, ShaderVariants::TEXTURED)];
+    for (usage, feature) in features {
+        if attribs.usage(usage) {
+            shader.define(feature, "1");
+        }
+    }
+}
+
+/// Creates a forward shading material for meshes with `attribs`, with the map features in `maps`, e.g.
+/// `&["ALBEDO_MAP", "NORMAL_MAP"]` (see `shaders::PBR_FRAG` and `shaders::BLINN_PHONG_FRAG`). The maps are set
+/// with `Material::set_texture`, using the feature name in camel case, e.g. `albedoMap`. Color maps (albedo
+/// and emissive) should be loaded as sRGB, see `Texture::from_path_srgb`. PBR materials also accept `IBL`, lighting
+/// them with `LightList::environment` instead of the ambient color.
+///
+/// Starts out white without emission or alpha cutoff; PBR materials non-metallic with a roughness of 0.5,
+/// Blinn-Phong ones with a white specular color and a shininess of 32.
+pub fn lit_material(model: ShadingModel, attribs: &VertexAttributes, maps: &[&str]) -> Material {
+    let mut shader = ShaderProgram::new();
+    define_attributes(&mut shader, attribs);
+    for map in maps {
+        shader.define(map, "1");
+    }
+    shader.create_vertex_shader(shaders::LIT_VERT);
+    shader.include(shaders::LIGHTING_GLSL);
+    shader.include(shaders::LIGHTS_GLSL);
+    if model == ShadingModel::Pbr && maps.contains(&"IBL") {
+        shader.include(shaders::IBL_GLSL);
+    }
+    shader.create_fragment_shader(match model {
+        ShadingModel::BlinnPhong => shaders::BLINN_PHONG_FRAG,
+        ShadingModel::Pbr => shaders::PBR_FRAG,
+    });
+    shader.link();
+    shader.bind_storage_block("Lights", LIGHTS_BINDING);
+    
+    let material = Material::new(Rc::new(shader))
+        .with_uniform("albedo", UniformValue::Vec4([1.0, 1.0, 1.0, 1.0]))
+        .with_uniform("emissive", UniformValue::Vec3([0.0, 0.0, 0.0]))
+        .with_uniform("alphaCutoff", 0.0);
+    match model {
+        ShadingModel::BlinnPhong => material
+            .with_uniform("specular", UniformValue::Vec3([1.0, 1.0, 1.0]))
+            .with_uniform("shininess", 32.0),
+        ShadingModel::Pbr => material
+            .with_uniform("metallic", 0.0)
+            .with_uniform("roughness", 0.5),
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use cgmath::Matrix4;
+use gl::types::*;
+use image::ImageError;
+use serde::{Deserialize, Serialize};
+use crate::graphics::{GlTexture, Mesh, ShaderProgram, Texture};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum UniformValue {
+    Float(f32),
+    Int(i32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    Mat4([[f32; 4]; 4]),
+}
+impl UniformValue {
+    pub fn apply(&self, shader: &ShaderProgram, name: &str) {
+        match *self {
+            UniformValue::Float(v) => shader.set_uniform1f32(name, v),
+            UniformValue::Int(v) => shader.set_uniform1i32(name, v),
+            UniformValue::Vec2(v) => shader.set_uniform2f32(name, v[0], v[1]),
+            UniformValue::Vec3(v) => shader.set_uniform3f32(name, v[0], v[1], v[2]),
+            UniformValue::Vec4(v) => shader.set_uniform4f32(name, v[0], v[1], v[2], v[3]),
+            UniformValue::Mat4(v) => shader.set_uniform_mat4f(name, Matrix4::from(v)),
+        }
+    }
+}
+impl From<f32> for UniformValue {
+    fn from(v: f32) -> Self {
+        UniformValue::Float(v)
+    }
+}
+impl From<i32> for UniformValue {
+    fn from(v: i32) -> Self {
+        UniformValue::Int(v)
+    }
+}
+impl From<Matrix4<f32>> for UniformValue {
+    fn from(v: Matrix4<f32>) -> Self {
+        UniformValue::Mat4(v.into())
+    }
+}
+
+#[derive(Debug)]
+pub enum MaterialError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, ron::Error),
+    /// A shader failed to compile or link, with the driver's log
+    Shader(String),
+    Texture(PathBuf, ImageError),
+}
+impl Display for MaterialError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MaterialError::Io(path, err) => write!(f, "Error reading material {:?}: {}", path, err),
+            MaterialError::Parse(path, err) => write!(f, "Error parsing material {:?}: {}", path, err),
+            MaterialError::Shader(err) => write!(f, "Error building material shader: {}", err),
+            MaterialError::Texture(path, err) => write!(f, "Error loading material texture {:?}: {}", path, err),
+        }
+    }
+}
+impl std::error::Error for MaterialError {}
+
+/// Layout of a `.ron` material description. Paths are relative to the description file.
+///
+/// ```ron
+/// (
+///     vertex: "shaders/lit.vert",
+///     fragment: "shaders/lit.frag",
+///     defines: { "FOG": "1" },
+///     uniforms: { "shininess": Float(32.0), "tint": Vec4((1.0, 0.8, 0.8, 1.0)) },
+///     textures: [ ("diffuseMap", "textures/brick.png") ],
+/// )
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaterialDesc {
+    pub vertex: PathBuf,
+    pub fragment: PathBuf,
+    /// Sorted, so the preprocessed sources (and program binary cache keys) are the same on every run
+    #[serde(default)]
+    pub defines: BTreeMap<String, String>,
+    #[serde(default)]
+    pub uniforms: HashMap<String, UniformValue>,
+    #[serde(default)]
+    pub textures: Vec<(String, PathBuf)>,
+}
+
+/// A shader together with the uniform values and textures it should be rendered with.
+///
+/// Textures are bound to consecutive texture units in the order they were added, and their sampler
+/// uniforms are set to match.
+pub struct Material {
+    pub shader: Rc<ShaderProgram>,
+    pub uniforms: HashMap<String, UniformValue>,
+    pub textures: Vec<(String, Rc<dyn GlTexture>)>,
+}
+impl Material {
+    pub fn new(shader: Rc<ShaderProgram>) -> Self {
+        Self {
+            shader,
+            uniforms: HashMap::new(),
+            textures: Vec::new(),
+        }
+    }
+    
+    pub fn from_path(path: &Path) -> Result<Self, MaterialError> {
+        let data = std::fs::read_to_string(path).map_err(|err| MaterialError::Io(path.to_path_buf(), err))?;
+        let desc: MaterialDesc = ron::from_str(&data).map_err(|err| MaterialError::Parse(path.to_path_buf(), err))?;
+        
+        Self::from_desc(&desc, path.parent().unwrap_or(Path::new("")))
+    }
+    
+    pub fn from_desc(desc: &MaterialDesc, base_dir: &Path) -> Result<Self, MaterialError> {
+        let read = |file: &PathBuf| {
+            let path = base_dir.join(file);
+            std::fs::read_to_string(&path).map_err(|err| MaterialError::Io(path, err))
+        };
+        
+        let mut shader = ShaderProgram::new();
+        for (name, value) in &desc.defines {
+            shader.define(name, value);
+        }
+        shader.create_vertex_shader(&read(&desc.vertex)?);
+        shader.create_fragment_shader(&read(&desc.fragment)?);
+        shader.try_link().map_err(MaterialError::Shader)?;
+        
+        let mut material = Self::new(Rc::new(shader));
+        material.uniforms = desc.uniforms.clone();
+        for (sampler, file) in &desc.textures {
+            let path = base_dir.join(file);
+            let texture = Texture::try_from_path(&path).map_err(|err| match err {
+                ImageError::IoError(err) => MaterialError::Io(path.clone(), err),
+                err => MaterialError::Texture(path.clone(), err),
+            })?;
+            material.textures.push((sampler.clone(), Rc::new(texture) as Rc<dyn GlTexture>));
+        }
+        
+        Ok(material)
+    }
+    
+    pub fn with_uniform<V: Into<UniformValue>>(mut self, name: &str, value: V) -> Self {
+        self.set_uniform(name, value);
+        
+        self
+    }
+    
+    pub fn with_texture(mut self, sampler: &str, texture: Rc<dyn GlTexture>) -> Self {
+        self.set_texture(sampler, texture);
+        
+        self
+    }
+    
+    pub fn set_uniform<V: Into<UniformValue>>(&mut self, name: &str, value: V) {
+        self.uniforms.insert(name.to_owned(), value.into());
+    }
+    
+    /// Sets the texture for `sampler`, replacing any texture already assigned to it.
+    pub fn set_texture(&mut self, sampler: &str, texture: Rc<dyn GlTexture>) {
+        match self.textures.iter_mut().find(|(name, _)| name == sampler) {
+            Some(slot) => slot.1 = texture,
+            None => self.textures.push((sampler.to_owned(), texture)),
+        }
+    }
+    
+    /// Creates a set of per-instance overrides on top of this material
+    pub fn instance(&self) -> MaterialInstance<'_> {
+        MaterialInstance {
+            material: self,
+            uniforms: HashMap::new(),
+            textures: Vec::new(),
+        }
+    }
+    
+    /// Binds the shader, sets all uniforms and binds all textures.
+    pub fn apply(&self) {
+        self.apply_with(&HashMap::new(), &[]);
+    }
+    
+    fn apply_with(&self, uniforms: &HashMap<String, UniformValue>, textures: &[(String, Rc<dyn GlTexture>)]) {
+        self.shader.bind();
+        
+        for (name, value) in &self.uniforms {
+            if !uniforms.contains_key(name) {
+                value.apply(&self.shader, name);
+            }
+        }
+        for (name, value) in uniforms {
+            value.apply(&self.shader, name);
+        }
+        
+        for (unit, (sampler, texture)) in self.textures.iter().enumerate() {
+            let texture = textures.iter().find(|(name, _)| name == sampler).map(|(_, tex)| tex).unwrap_or(texture);
+            texture.bind_to(unit as u32);
+            self.shader.set_uniform1i32(sampler, unit as i32);
+        }
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+    
+    /// Applies this material and renders `mesh` with it.
+    pub fn render(&self, mesh: &mut Mesh, primitive: GLenum, proj_model_view: Matrix4<f32>) {
+        self.apply();
+        mesh.render(&self.shader, true, primitive, proj_model_view);
+        self.shader.unbind();
+    }
+}
+
+/// Uniform and texture overrides for a single object using a shared `Material`.
+///
+/// Only textures whose sampler already exists in the material can be overridden.
+pub struct MaterialInstance<'a> {
+    pub material: &'a Material,
+    pub uniforms: HashMap<String, UniformValue>,
+    pub textures: Vec<(String, Rc<dyn GlTexture>)>,
+}
+impl<'a> MaterialInstance<'a> {
+    pub fn with_uniform<V: Into<UniformValue>>(mut self, name: &str, value: V) -> Self {
+        self.uniforms.insert(name.to_owned(), value.into());
+        
+        self
+    }
+    
+    pub fn with_texture(mut self, sampler: &str, texture: Rc<dyn GlTexture>) -> Self {
+        self.textures.retain(|(name, _)| name != sampler);
+        self.textures.push((sampler.to_owned(), texture));
+        
+        self
+    }
+    
+    pub fn apply(&self) {
+        self.material.apply_with(&self.uniforms, &self.textures);
+    }
+    
+    pub fn render(&self, mesh: &mut Mesh, primitive: GLenum, proj_model_view: Matrix4<f32>) {
+        self.apply();
+        mesh.render(&self.material.shader, true, primitive, proj_model_view);
+        self.material.shader.unbind();
+    }
+}
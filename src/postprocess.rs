@@ -0,0 +1,358 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use gl::types::*;
+use crate::framebuffer::{Attachment, Framebuffer, FramebufferError, Renderbuffer};
+use crate::graphics::{GlTexture, ShaderProgram, Texture, TextureFormat, TextureParams, Wrap, UNIFORM_TEXTURE_SAMPLER};
+use crate::material::Material;
+use crate::shaders;
+use crate::texture::Texture3D;
+
+/// Curve used by `Effect::ToneMap` to map HDR colors to the 0-1 range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMapOperator {
+    /// `x / (1 + x)`, never fully saturates
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve, with more contrast and saturated highlights
+    Aces,
+    /// Hable's Uncharted 2 curve
+    Filmic,
+}
+impl ToneMapOperator {
+    fn index(&self) -> i32 {
+        match self {
+            ToneMapOperator::Reinhard => 0,
+            ToneMapOperator::Aces => 1,
+            ToneMapOperator::Filmic => 2,
+        }
+    }
+}
+
+/// A fullscreen pass run by `PostProcessor`. Parameters can be changed between frames through
+/// `PostProcessor::effects`.
+pub enum Effect {
+    /// Fast approximate antialiasing, best placed after tone mapping
+    Fxaa,
+    /// Adds a blurred copy of the pixels brighter than `threshold`. The bright pixels are blurred `passes`
+    /// times at half resolution, with taps `radius` pixels apart.
+    Bloom { threshold: f32, intensity: f32, radius: f32, passes: u32 },
+    /// Scales colors by `exposure`, maps them to 0-1 with `operator` and raises them to `1 / gamma`. Use a gamma
+    /// of 1 when the output is an sRGB framebuffer (see `Screen::new_srgb`), which encodes colors itself,
+    /// and 2.2 otherwise.
+    ToneMap { exposure: f32, operator: ToneMapOperator, gamma: f32 },
+    /// Remaps colors through a LUT made with `Texture3D::from_lut_image`, blended by `strength` (0-1)
+    ColorGrade { lut: Rc<Texture3D>, strength: f32 },
+    /// Darkens pixels further than `radius` from the center (1 at the corners), over a `smoothness` wide falloff
+    Vignette { intensity: f32, radius: f32, smoothness: f32 },
+    /// Offsets red and blue by up to `amount` (in texture coordinates) at the edges
+    ChromaticAberration { amount: f32 },
+    GaussianBlur { radius: f32 },
+    /// A material made with `PostProcessor::custom_pass`. The previous pass's output is bound to
+    /// `textureSampler`, on the texture unit after the material's own textures, and its texel size to
+    /// `texelSize` (vec2).
+    Custom(Material),
+}
+
+/// Renders the scene into an offscreen target, then runs `effects` in order as fullscreen passes,
+/// ping-ponging between two textures. The last pass writes to the framebuffer that was bound before `begin`.
+///
+/// ```ignore
+/// post.begin();
+/// // draw the scene as usual
+/// post.end();
+/// ```
+pub struct PostProcessor {
+    pub effects: Vec<Effect>,
+    format: TextureFormat,
+    samples: u32,
+    scene: Framebuffer,
+    /// Resolve target of a multisampled `scene`
+    resolved: Option<Framebuffer>,
+    ping: [Framebuffer; 2],
+    /// Intermediate target of the two-pass blur
+    scratch: Framebuffer,
+    /// Half resolution targets for bloom
+    bloom: [Framebuffer; 2],
+    shaders: RefCell<HashMap<&'static str, Rc<ShaderProgram>>>,
+    vao: GLuint,
+}
+impl PostProcessor {
+    /// Post-processing in 8-bit RGBA without multisampling, see `with_options`.
+    pub fn new(width: u32, height: u32) -> Result<Self, FramebufferError> {
+        Self::with_options(width, height, TextureFormat::Rgba8, 0)
+    }
+    
+    /// Post-processing in 16-bit float, so lighting can go above 1 until an `Effect::ToneMap` pass.
+    pub fn hdr(width: u32, height: u32, samples: u32) -> Result<Self, FramebufferError> {
+        Self::with_options(width, height, TextureFormat::Rgba16F, samples)
+    }
+    
+    /// `format` is used for the scene and every intermediate texture, e.g. `Rgba16F` for HDR. If `samples` is
+    /// above 0 the scene is rendered with MSAA and resolved before the first pass.
+    pub fn with_options(width: u32, height: u32, format: TextureFormat, samples: u32) -> Result<Self, FramebufferError> {
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        
+        let (scene, resolved) = Self::scene_targets(width, height, format, samples)?;
+        Ok(Self {
+            effects: Vec::new(),
+            format,
+            samples,
+            scene,
+            resolved,
+            ping: [target(width, height, format)?, target(width, height, format)?],
+            scratch: target(width, height, format)?,
+            bloom: [target(half(width), half(height), format)?, target(half(width), half(height), format)?],
+            shaders: RefCell::new(HashMap::new()),
+            vao,
+        })
+    }
+    
+    fn scene_targets(width: u32, height: u32, format: TextureFormat, samples: u32) -> Result<(Framebuffer, Option<Framebuffer>), FramebufferError> {
+        if samples > 0 {
+            let scene = Framebuffer::create_multisampled(width, height, format, Some(TextureFormat::Depth24Stencil8), samples)?;
+            
+            return Ok((scene, Some(target(width, height, format)?)));
+        }
+        
+        let mut scene = target(width, height, format)?;
+        scene.attach_depth(Attachment::Renderbuffer(Renderbuffer::new(width, height, TextureFormat::Depth24Stencil8)))?;
+        scene.check()?;
+        
+        Ok((scene, None))
+    }
+    
+    pub fn with_effect(mut self, effect: Effect) -> Self {
+        self.effects.push(effect);
+        
+        self
+    }
+    
+    /// Creates a material for an `Effect::Custom` pass from a fragment shader. The shader receives
+    /// `vTexCoord` from the fullscreen vertex shader and should sample `textureSampler`.
+    pub fn custom_pass(fragment: &str) -> Material {
+        let mut shader = ShaderProgram::new();
+        shader.create_vertex_shader(shaders::FULLSCREEN_VERT);
+        shader.create_fragment_shader(fragment);
+        shader.link();
+        
+        Material::new(Rc::new(shader))
+    }
+    
+    /// Recreates all targets at the new size, e.g. when the window is resized.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        if (width, height) == (self.scene.width, self.scene.height) {
+            return Ok(());
+        }
+        
+        let (scene, resolved) = Self::scene_targets(width, height, self.format, self.samples)?;
+        self.scene = scene;
+        self.resolved = resolved;
+        for fb in self.ping.iter_mut().chain(std::iter::once(&mut self.scratch)) {
+            fb.resize(width, height)?;
+        }
+        for fb in &mut self.bloom {
+            fb.resize(half(width), half(height))?;
+        }
+        
+        Ok(())
+    }
+    
+    /// The offscreen scene target, e.g. to read its depth attachment.
+    pub fn scene(&self) -> &Framebuffer {
+        &self.scene
+    }
+    
+    /// Redirects rendering to the scene target and clears it with the current clear color.
+    pub fn begin(&self) {
+        self.scene.bind();
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+        }
+    }
+    
+    /// Runs every effect and writes the result to the framebuffer that was bound before `begin`.
+    pub fn end(&self) {
+        self.scene.unbind();
+        self.run(None);
+    }
+    
+    /// Same as `end`, writing the result to `target` instead.
+    pub fn end_to(&self, target: &Framebuffer) {
+        self.scene.unbind();
+        self.run(Some(target));
+    }
+    
+    fn run(&self, output: Option<&Framebuffer>) {
+        let source = match &self.resolved {
+            Some(resolved) => {
+                self.scene.resolve_to(resolved);
+                resolved
+            },
+            None => &self.scene,
+        };
+        
+        let (depth_test, blend) = unsafe { (gl::IsEnabled(gl::DEPTH_TEST), gl::IsEnabled(gl::BLEND)) };
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::BLEND);
+        }
+        
+        let mut input = source.color(0);
+        if self.effects.is_empty() {
+            self.draw(&self.shader(shaders::COPY_FRAG), output, |shader| bind_input(shader, UNIFORM_TEXTURE_SAMPLER, input, 0));
+        }
+        
+        let mut next = 0;
+        for (i, effect) in self.effects.iter().enumerate() {
+            let last = i == self.effects.len() - 1;
+            let target = if last { output } else { Some(&self.ping[next]) };
+            self.apply(effect, input, target);
+            
+            if !last {
+                input = self.ping[next].color(0);
+                next = 1 - next;
+            }
+        }
+        
+        unsafe {
+            if depth_test == gl::TRUE {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+            if blend == gl::TRUE {
+                gl::Enable(gl::BLEND);
+            }
+        }
+    }
+    
+    fn apply(&self, effect: &Effect, input: &Texture, target: Option<&Framebuffer>) {
+        let texel = [1.0 / input.width as f32, 1.0 / input.height as f32];
+        let simple = |frag: &'static str, uniforms: &dyn Fn(&ShaderProgram)| {
+            self.draw(&self.shader(frag), target, |shader| {
+                bind_input(shader, UNIFORM_TEXTURE_SAMPLER, input, 0);
+                shader.set_uniform2f32("texelSize", texel[0], texel[1]);
+                uniforms(shader);
+            });
+        };
+        
+        match effect {
+            Effect::Fxaa => simple(shaders::FXAA_FRAG, &|_| ()),
+            Effect::ToneMap { exposure, operator, gamma } => simple(shaders::TONEMAP_FRAG, &|shader| {
+                shader.set_uniform1f32("exposure", *exposure);
+                shader.set_uniform1i32("toneMapOperator", operator.index());
+                shader.set_uniform1f32("gamma", *gamma);
+            }),
+            Effect::Vignette { intensity, radius, smoothness } => simple(shaders::VIGNETTE_FRAG, &|shader| {
+                shader.set_uniform1f32("intensity", *intensity);
+                shader.set_uniform1f32("radius", *radius);
+                shader.set_uniform1f32("smoothness", *smoothness);
+            }),
+            Effect::ChromaticAberration { amount } => simple(shaders::CHROMATIC_ABERRATION_FRAG, &|shader| shader.set_uniform1f32("amount", *amount)),
+            Effect::ColorGrade { lut, strength } => simple(shaders::COLOR_GRADE_FRAG, &|shader| {
+                bind_input(shader, "lut", lut.as_ref(), 1);
+                shader.set_uniform1f32("lutSize", lut.width as f32);
+                shader.set_uniform1f32("strength", *strength);
+            }),
+            Effect::GaussianBlur { radius } => {
+                self.blur(input, &self.scratch, target, *radius);
+            },
+            Effect::Bloom { threshold, intensity, radius, passes } => {
+                self.draw(&self.shader(shaders::BLOOM_THRESHOLD_FRAG), Some(&self.bloom[0]), |shader| {
+                    bind_input(shader, UNIFORM_TEXTURE_SAMPLER, input, 0);
+                    shader.set_uniform1f32("threshold", *threshold);
+                });
+                for _ in 0..*passes {
+                    self.blur(self.bloom[0].color(0), &self.bloom[1], Some(&self.bloom[0]), *radius);
+                }
+                
+                simple(shaders::BLOOM_COMPOSITE_FRAG, &|shader| {
+                    bind_input(shader, "bloomSampler", self.bloom[0].color(0), 1);
+                    shader.set_uniform1f32("intensity", *intensity);
+                });
+            },
+            Effect::Custom(material) => {
+                let unit = material.textures.len() as u32;
+                self.draw(&material.shader, target, |shader| {
+                    material.apply();
+                    bind_input(shader, UNIFORM_TEXTURE_SAMPLER, input, unit);
+                    shader.set_uniform2f32("texelSize", texel[0], texel[1]);
+                });
+            },
+        }
+    }
+    
+    /// Separable gaussian blur of `input` into `target`, through `scratch`.
+    fn blur(&self, input: &Texture, scratch: &Framebuffer, target: Option<&Framebuffer>, radius: f32) {
+        let shader = self.shader(shaders::BLUR_FRAG);
+        self.draw(&shader, Some(scratch), |shader| {
+            bind_input(shader, UNIFORM_TEXTURE_SAMPLER, input, 0);
+            shader.set_uniform2f32("direction", radius / input.width as f32, 0.0);
+        });
+        self.draw(&shader, target, |shader| {
+            bind_input(shader, UNIFORM_TEXTURE_SAMPLER, scratch.color(0), 0);
+            shader.set_uniform2f32("direction", 0.0, radius / input.height as f32);
+        });
+    }
+    
+    /// Draws a fullscreen triangle with `shader` into `target`, or the currently bound framebuffer.
+    /// `setup` binds inputs and sets uniforms once the shader is bound.
+    fn draw(&self, shader: &ShaderProgram, target: Option<&Framebuffer>, setup: impl FnOnce(&ShaderProgram)) {
+        if let Some(target) = target {
+            target.bind();
+        }
+        
+        shader.bind();
+        setup(shader);
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+        shader.unbind();
+        
+        if let Some(target) = target {
+            target.unbind();
+        }
+    }
+    
+    /// Built-in pass shader for `fragment`, compiled on first use.
+    fn shader(&self, fragment: &'static str) -> Rc<ShaderProgram> {
+        self.shaders.borrow_mut().entry(fragment).or_insert_with(|| {
+            let mut shader = ShaderProgram::new();
+            shader.create_vertex_shader(shaders::FULLSCREEN_VERT);
+            shader.create_fragment_shader(fragment);
+            shader.link();
+            
+            Rc::new(shader)
+        }).clone()
+    }
+}
+impl Drop for PostProcessor {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+fn bind_input(shader: &ShaderProgram, sampler: &str, texture: &dyn GlTexture, unit: u32) {
+    texture.bind_to(unit);
+    shader.set_uniform1i32(sampler, unit as i32);
+}
+
+/// Color-only target sampled with linear filtering and clamped edges
+fn target(width: u32, height: u32, format: TextureFormat) -> Result<Framebuffer, FramebufferError> {
+    let params = TextureParams::linear().with_wrap(Wrap::ClampToEdge);
+    let mut fb = Framebuffer::new(width, height);
+    fb.attach_color(Attachment::Texture(Texture::empty(width, height, format, params)))?;
+    fb.check()?;
+    
+    Ok(fb)
+}
+
+fn half(size: u32) -> u32 {
+    (size / 2).max(1)
+}
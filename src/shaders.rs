@@ -0,0 +1,112 @@
+//! GLSL sources bundled with the crate.
+//!
+//! These follow the conventions in `graphics`: attribute locations `ATTRIB_*` and uniform names `UNIFORM_*`.
+
+/// Vertex shader for `MeshRenderer`: position and color attributes.
+pub const MESH_VERT: &str = include_str!("shaders/mesh.vert");
+/// Fragment shader for `MeshRenderer`: outputs the interpolated vertex color.
+pub const MESH_FRAG: &str = include_str!("shaders/mesh.frag");
+
+/// Vertex shader for `TextureRenderer`: position, color and texture coordinate attributes.
+pub const TEXTURE_VERT: &str = include_str!("shaders/texture.vert");
+/// Fragment shader for `TextureRenderer`: samples `textureSampler`, multiplied by the vertex color.
+pub const TEXTURE_FRAG: &str = include_str!("shaders/texture.frag");
+
+/// Vertex shader drawing one triangle covering the viewport, from `gl_VertexID` alone. Outputs `vTexCoord`.
+pub const FULLSCREEN_VERT: &str = include_str!("shaders/fullscreen.vert");
+/// Fragment shader copying `textureSampler` texel for texel, multiplied by the `tint` uniform (vec4).
+pub const TINT_FRAG: &str = include_str!("shaders/tint.frag");
+
+/// Fragment shader copying `textureSampler` at `vTexCoord`. Use with `FULLSCREEN_VERT` for resampling copies.
+pub const COPY_FRAG: &str = include_str!("shaders/copy.frag");
+/// Post-processing: FXAA antialiasing. Uniforms: `texelSize` (vec2).
+pub const FXAA_FRAG: &str = include_str!("shaders/fxaa.frag");
+/// Post-processing: one direction of a separable gaussian blur. Uniforms: `direction` (vec2).
+pub const BLUR_FRAG: &str = include_str!("shaders/blur.frag");
+/// Post-processing: bright pass for bloom. Uniforms: `threshold` (float).
+pub const BLOOM_THRESHOLD_FRAG: &str = include_str!("shaders/bloom_threshold.frag");
+/// Post-processing: adds `bloomSampler` to `textureSampler`. Uniforms: `intensity` (float).
+pub const BLOOM_COMPOSITE_FRAG: &str = include_str!("shaders/bloom_composite.frag");
+/// Post-processing: HDR to LDR tone mapping. Uniforms: `exposure` and `gamma` (float), `toneMapOperator` (int,
+/// 0 Reinhard, 1 ACES, 2 filmic).
+pub const TONEMAP_FRAG: &str = include_str!("shaders/tonemap.frag");
+/// Post-processing: 3D LUT color grading. Uniforms: `lut` (sampler3D), `lutSize` and `strength` (float).
+pub const COLOR_GRADE_FRAG: &str = include_str!("shaders/color_grade.frag");
+/// Post-processing: darkens the edges. Uniforms: `intensity`, `radius` and `smoothness` (float).
+pub const VIGNETTE_FRAG: &str = include_str!("shaders/vignette.frag");
+/// Post-processing: splits color channels towards the edges. Uniforms: `amount` (float).
+pub const CHROMATIC_ABERRATION_FRAG: &str = include_str!("shaders/chromatic_aberration.frag");
+
+/// Vertex shader for depth only passes, used by `shadow` to render shadow maps. Position attribute only.
+pub const SHADOW_DEPTH_VERT: &str = include_str!("shaders/shadow_depth.vert");
+/// Fragment shader writing no color, for use with `SHADOW_DEPTH_VERT`.
+pub const SHADOW_DEPTH_FRAG: &str = include_str!("shaders/shadow_depth.frag");
+/// Snippet for `ShaderProgram::include`: `pcf2D` and `pcfArray` filtered shadow lookups. Define
+/// `SHADOW_PCF_RADIUS` (1 by default) to change the kernel size.
+pub const SHADOW_PCF_GLSL: &str = include_str!("shaders/shadow_pcf.glsl");
+/// Snippet for `ShaderProgram::include`: `directionalShadow(worldPos, viewDepth, bias)` and the uniforms set by
+/// `DirectionalShadow::bind`. Include after `SHADOW_PCF_GLSL`.
+pub const SHADOW_CASCADES_GLSL: &str = include_str!("shaders/shadow_cascades.glsl");
+/// Snippet for `ShaderProgram::include`: `spotShadow(worldPos, bias)` and the uniforms set by
+/// `SpotShadow::bind`. Include after `SHADOW_PCF_GLSL`.
+pub const SHADOW_SPOT_GLSL: &str = include_str!("shaders/shadow_spot.glsl");
+
+/// Snippet for `ShaderProgram::include`: light attenuation, the metallic-roughness BRDF `shadePBR` and
+/// `perturbNormal` for normal maps without tangents.
+pub const LIGHTING_GLSL: &str = include_str!("shaders/lighting.glsl");
+/// Vertex shader for lit meshes, passing world space positions and normals to `PBR_FRAG`, `BLINN_PHONG_FRAG` and
+/// `GBUFFER_FRAG`. Uniforms: `projModelView` and `model` (mat4). Features: `ShaderVariants::VERTEX_COLORS`,
+/// `NORMALS` and `TEXTURED`.
+pub const LIT_VERT: &str = include_str!("shaders/lit.vert");
+/// Deferred geometry pass. Uniforms: `albedo` (vec4), `metallic`, `roughness` and `alphaCutoff` (float). Features:
+/// `ALBEDO_MAP`, `NORMAL_MAP`, `METALLIC_ROUGHNESS_MAP`, `OCCLUSION_MAP`. Include `LIGHTING_GLSL`.
+pub const GBUFFER_FRAG: &str = include_str!("shaders/gbuffer.frag");
+/// Deferred lighting: positions of a light volume. Uniforms: `projModelView`.
+pub const DEFERRED_VOLUME_VERT: &str = include_str!("shaders/deferred_volume.vert");
+/// Deferred lighting: shades the G-buffer for one light. Include `LIGHTING_GLSL`.
+pub const DEFERRED_LIGHT_FRAG: &str = include_str!("shaders/deferred_light.frag");
+/// Shows a single G-buffer channel. Uniforms: `channel` (int), `near` and `far` (float), `perspective` (bool).
+pub const GBUFFER_DEBUG_FRAG: &str = include_str!("shaders/gbuffer_debug.frag");
+/// Snippet for `ShaderProgram::include`: the `Lights` storage block and uniforms set by `LightList::bind`, and
+/// `lightRadiance`. Include after `LIGHTING_GLSL`.
+pub const LIGHTS_GLSL: &str = include_str!("shaders/lights.glsl");
+/// Forward metallic-roughness shading. Uniforms: `albedo` (vec4), `metallic`, `roughness` and `alphaCutoff`
+/// (float), `emissive` (vec3). Features: `ALBEDO_MAP`, `NORMAL_MAP`, `METALLIC_ROUGHNESS_MAP`, `OCCLUSION_MAP`,
+/// `EMISSIVE_MAP`, `IBL` (also include `IBL_GLSL`). Include `LIGHTING_GLSL` and `LIGHTS_GLSL`.
+pub const PBR_FRAG: &str = include_str!("shaders/pbr.frag");
+/// Forward Blinn-Phong shading. Uniforms: `albedo` (vec4), `specular` and `emissive` (vec3), `shininess` and
+/// `alphaCutoff` (float). Features: `ALBEDO_MAP`, `NORMAL_MAP`, `SPECULAR_MAP`, `EMISSIVE_MAP`. Include
+/// `LIGHTING_GLSL` and `LIGHTS_GLSL`.
+pub const BLINN_PHONG_FRAG: &str = include_str!("shaders/blinn_phong.frag");
+/// Snippet for `ShaderProgram::include`: `ambientIBL` and the uniforms set by `Environment::bind`. Include after
+/// `LIGHTING_GLSL`.
+pub const IBL_GLSL: &str = include_str!("shaders/ibl.glsl");
+/// IBL precomputation: `cubeDirection`, Hammersley points and GGX importance sampling, for the `IBL_*_FRAG` passes.
+pub const IBL_COMMON_GLSL: &str = include_str!("shaders/ibl_common.glsl");
+/// IBL precomputation: equirectangular panorama to cube map face. Uniforms: `panorama` (sampler2D), `face` (int).
+pub const IBL_EQUIRECT_FRAG: &str = include_str!("shaders/ibl_equirect.frag");
+/// IBL precomputation: diffuse irradiance. Uniforms: `environment` (samplerCube), `face` (int).
+pub const IBL_IRRADIANCE_FRAG: &str = include_str!("shaders/ibl_irradiance.frag");
+/// IBL precomputation: specular prefiltering. Uniforms: `environment` (samplerCube), `face` (int), `roughness` and
+/// `environmentSize` (float), `sampleCount` (int).
+pub const IBL_PREFILTER_FRAG: &str = include_str!("shaders/ibl_prefilter.frag");
+/// IBL precomputation: split sum BRDF lookup table. Uniforms: `sampleCount` (int).
+pub const IBL_BRDF_FRAG: &str = include_str!("shaders/ibl_brdf.frag");
+
+/// Vertex shader for `Skybox`: a fullscreen triangle at the far plane. Uniforms: `invViewProj` (mat4). Outputs
+/// `vDirection`.
+pub const SKYBOX_VERT: &str = include_str!("shaders/skybox.vert");
+/// Fragment shader for `Skybox`. Uniforms: `skyMode` (int), `intensity` (float), `skyCube` and `skyPanorama`
+/// samplers, and the gradient and atmosphere parameters set by `Skybox::render`.
+pub const SKYBOX_FRAG: &str = include_str!("shaders/skybox.frag");
+
+/// Fragment shader for `Ssao`: hemisphere sampled ambient occlusion in view space. Uniforms: `depthMap`,
+/// `normalMap` and `noiseMap` (sampler2D), `projection`, `invProjection` and `view` (mat4), `kernel` (vec3[64]),
+/// `sampleCount` (int), `noiseScale` (vec2), `radius`, `bias` and `power` (float).
+pub const SSAO_FRAG: &str = include_str!("shaders/ssao.frag");
+/// Fragment shader for `Ssao`: one direction of a bilateral blur. Uniforms: `occlusionMap` and `depthMap`
+/// (sampler2D), `invProjection` (mat4), `direction` (vec2), `sharpness` (float).
+pub const SSAO_BLUR_FRAG: &str = include_str!("shaders/ssao_blur.frag");
+/// Fragment shader for `Ssao::apply`: the occlusion as a color to blend with. Uniforms: `occlusionMap`
+/// (sampler2D), `strength` (float).
+pub const SSAO_COMPOSITE_FRAG: &str = include_str!("shaders/ssao_composite.frag");
@@ -0,0 +1,78 @@
+#version 460 core
+
+// Forward Blinn-Phong shading, lit by a `LightList`. Maps are enabled with ALBEDO_MAP, NORMAL_MAP, SPECULAR_MAP
+// (specular color in RGB) and EMISSIVE_MAP, and multiplied with the uniform values. Requires the lighting
+// functions and the light list.
+
+in vec3 vWorldPos;
+in vec3 vNormal;
+in vec4 vColor;
+in vec2 vTexCoord;
+
+uniform vec4 albedo;
+uniform vec3 specular;
+uniform float shininess;
+uniform vec3 emissive;
+// fragments with a lower alpha are discarded
+uniform float alphaCutoff;
+
+#ifdef ALBEDO_MAP
+uniform sampler2D albedoMap;
+#endif
+#ifdef NORMAL_MAP
+uniform sampler2D normalMap;
+#endif
+#ifdef SPECULAR_MAP
+uniform sampler2D specularMap;
+#endif
+#ifdef EMISSIVE_MAP
+uniform sampler2D emissiveMap;
+#endif
+
+out vec4 fragColor;
+
+void main() {
+    vec4 base = albedo * vColor;
+#ifdef ALBEDO_MAP
+    base *= texture(albedoMap, vTexCoord);
+#endif
+    if (base.a < alphaCutoff) {
+        discard;
+    }
+    
+#ifdef NORMALS
+    vec3 N = normalize(vNormal);
+#else
+    vec3 N = normalize(cross(dFdx(vWorldPos), dFdy(vWorldPos)));
+#endif
+    if (!gl_FrontFacing) {
+        N = -N;
+    }
+#ifdef NORMAL_MAP
+    N = perturbNormal(N, vWorldPos, vTexCoord, texture(normalMap, vTexCoord).xyz * 2.0 - 1.0);
+#endif
+    
+    vec3 specularColor = specular;
+#ifdef SPECULAR_MAP
+    specularColor *= texture(specularMap, vTexCoord).rgb;
+#endif
+    
+    vec3 V = normalize(cameraPosition - vWorldPos);
+    vec3 color = ambient * base.rgb;
+    for (int i = 0; i < lightCount; i++) {
+        vec3 L;
+        vec3 radiance = lightRadiance(lights[i], vWorldPos, L);
+        float NdotL = max(dot(N, L), 0.0);
+        if (NdotL > 0.0) {
+            float NdotH = max(dot(N, normalize(L + V)), 0.0);
+            color += (base.rgb * NdotL + specularColor * pow(NdotH, shininess)) * radiance;
+        }
+    }
+    
+    vec3 emission = emissive;
+#ifdef EMISSIVE_MAP
+    emission *= texture(emissiveMap, vTexCoord).rgb;
+#endif
+    
+    fragColor = vec4(color + emission, base.a);
+}
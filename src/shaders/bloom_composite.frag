@@ -0,0 +1,16 @@
+#version 460 core
+
+in vec2 vTexCoord;
+
+uniform sampler2D textureSampler;
+uniform sampler2D bloomSampler;
+uniform float intensity;
+
+out vec4 fragColor;
+
+void main() {
+    vec4 color = texture(textureSampler, vTexCoord);
+    vec3 bloom = texture(bloomSampler, vTexCoord).rgb;
+    
+    fragColor = vec4(color.rgb + bloom * intensity, color.a);
+}
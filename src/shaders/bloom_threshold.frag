@@ -0,0 +1,22 @@
+#version 460 core
+
+// Keeps the part of each pixel brighter than `threshold`, with a soft knee to avoid hard edges.
+
+in vec2 vTexCoord;
+
+uniform sampler2D textureSampler;
+uniform float threshold;
+
+out vec4 fragColor;
+
+void main() {
+    vec3 color = texture(textureSampler, vTexCoord).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    
+    float knee = threshold * 0.5 + 1e-5;
+    float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    float contribution = max(soft, brightness - threshold) / max(brightness, 1e-5);
+    
+    fragColor = vec4(color * contribution, 1.0);
+}
@@ -0,0 +1,24 @@
+#version 460 core
+
+// One direction of a separable 9-tap gaussian blur, using linear filtering to take 5 samples.
+
+in vec2 vTexCoord;
+
+uniform sampler2D textureSampler;
+// Distance between taps in texture coordinates, e.g. (radius / width, 0) for the horizontal pass
+uniform vec2 direction;
+
+out vec4 fragColor;
+
+const float OFFSETS[3] = float[](0.0, 1.3846153846, 3.2307692308);
+const float WEIGHTS[3] = float[](0.2270270270, 0.3162162162, 0.0702702703);
+
+void main() {
+    vec4 color = texture(textureSampler, vTexCoord) * WEIGHTS[0];
+    for (int i = 1; i < 3; i++) {
+        color += texture(textureSampler, vTexCoord + direction * OFFSETS[i]) * WEIGHTS[i];
+        color += texture(textureSampler, vTexCoord - direction * OFFSETS[i]) * WEIGHTS[i];
+    }
+    
+    fragColor = color;
+}
@@ -0,0 +1,17 @@
+#version 460 core
+
+// Offsets the red and blue channels away from the center, growing towards the edges.
+
+in vec2 vTexCoord;
+
+uniform sampler2D textureSampler;
+uniform float amount;
+
+out vec4 fragColor;
+
+void main() {
+    vec2 offset = (vTexCoord - 0.5) * amount;
+    vec4 color = texture(textureSampler, vTexCoord);
+    
+    fragColor = vec4(texture(textureSampler, vTexCoord + offset).r, color.g, texture(textureSampler, vTexCoord - offset).b, color.a);
+}
@@ -0,0 +1,22 @@
+#version 460 core
+
+// Remaps colors through a 3D lookup table (see Texture3D::from_lut_image).
+
+in vec2 vTexCoord;
+
+uniform sampler2D textureSampler;
+uniform sampler3D lut;
+uniform float lutSize;
+uniform float strength;
+
+out vec4 fragColor;
+
+void main() {
+    vec4 color = texture(textureSampler, vTexCoord);
+    
+    // sample texel centers, so 0 and 1 map to the first and last LUT entries
+    vec3 coord = clamp(color.rgb, 0.0, 1.0) * ((lutSize - 1.0) / lutSize) + 0.5 / lutSize;
+    vec3 graded = texture(lut, coord).rgb;
+    
+    fragColor = vec4(mix(color.rgb, graded, strength), color.a);
+}
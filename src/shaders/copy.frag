@@ -0,0 +1,11 @@
+#version 460 core
+
+in vec2 vTexCoord;
+
+uniform sampler2D textureSampler;
+
+out vec4 fragColor;
+
+void main() {
+    fragColor = texture(textureSampler, vTexCoord);
+}
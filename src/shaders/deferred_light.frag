@@ -0,0 +1,64 @@
+#version 460 core
+
+// Lighting pass of the deferred renderer, one light per draw, added to the output. Compiled with one of AMBIENT,
+// DIRECTIONAL, POINT or SPOT defined. Requires the lighting functions.
+
+uniform sampler2D gAlbedo;
+uniform sampler2D gNormal;
+uniform sampler2D gMaterial;
+uniform sampler2D gDepth;
+uniform mat4 invViewProj;
+uniform vec3 cameraPosition;
+uniform vec2 screenSize;
+
+uniform vec3 ambient;
+// screen space ambient occlusion, multiplied with the G-buffer's when useOcclusionMap is set
+uniform sampler2D occlusionMap;
+uniform bool useOcclusionMap;
+// color times intensity
+uniform vec3 lightColor;
+uniform vec3 lightPosition;
+uniform vec3 lightDirection;
+uniform float lightRange;
+uniform float cosInner;
+uniform float cosOuter;
+
+out vec4 fragColor;
+
+void main() {
+    vec2 uv = gl_FragCoord.xy / screenSize;
+    float depth = texture(gDepth, uv).r;
+    if (depth == 1.0) {
+        discard;
+    }
+    vec4 albedo = texture(gAlbedo, uv);
+    
+#ifdef AMBIENT
+    float occlusion = albedo.a;
+    if (useOcclusionMap) {
+        occlusion *= texture(occlusionMap, uv).r;
+    }
+    fragColor = vec4(albedo.rgb * ambient * occlusion, 1.0);
+#else
+    vec4 world = invViewProj * vec4(uv * 2.0 - 1.0, depth * 2.0 - 1.0, 1.0);
+    vec3 pos = world.xyz / world.w;
+    vec3 N = normalize(texture(gNormal, uv).xyz);
+    vec2 material = texture(gMaterial, uv).rg;
+    vec3 V = normalize(cameraPosition - pos);
+    
+#ifdef DIRECTIONAL
+    vec3 L = -lightDirection;
+    float attenuation = 1.0;
+#else
+    vec3 toLight = lightPosition - pos;
+    float dist = length(toLight);
+    vec3 L = toLight / dist;
+    float attenuation = distanceAttenuation(dist, lightRange);
+#ifdef SPOT
+    attenuation *= spotAttenuation(L, lightDirection, cosInner, cosOuter);
+#endif
+#endif
+    
+    fragColor = vec4(shadePBR(N, V, L, albedo.rgb, material.r, material.g) * lightColor * attenuation, 1.0);
+#endif
+}
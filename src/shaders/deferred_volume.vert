@@ -0,0 +1,11 @@
+#version 460 core
+
+// Light volume of the deferred lighting pass, positions only
+
+layout(location = 0) in vec3 position;
+
+uniform mat4 projModelView;
+
+void main() {
+    gl_Position = projModelView * vec4(position, 1.0);
+}
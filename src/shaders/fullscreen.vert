@@ -0,0 +1,11 @@
+#version 460 core
+
+// Draws a single triangle covering the viewport. Use with glDrawArrays(GL_TRIANGLES, 0, 3) and no vertex buffers.
+
+out vec2 vTexCoord;
+
+void main() {
+    vec2 pos = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    vTexCoord = pos;
+    gl_Position = vec4(pos * 2.0 - 1.0, 0.0, 1.0);
+}
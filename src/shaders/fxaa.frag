@@ -0,0 +1,45 @@
+#version 460 core
+
+// Simplified FXAA, after Timothy Lottes' FXAA 3.11 console version. Expects tone mapped, gamma-space colors.
+
+in vec2 vTexCoord;
+
+uniform sampler2D textureSampler;
+uniform vec2 texelSize;
+
+out vec4 fragColor;
+
+const float SPAN_MAX = 8.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float REDUCE_MIN = 1.0 / 128.0;
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+void main() {
+    vec4 center = texture(textureSampler, vTexCoord);
+    float lumaM = luma(center.rgb);
+    float lumaNW = luma(texture(textureSampler, vTexCoord + vec2(-1.0, -1.0) * texelSize).rgb);
+    float lumaNE = luma(texture(textureSampler, vTexCoord + vec2(1.0, -1.0) * texelSize).rgb);
+    float lumaSW = luma(texture(textureSampler, vTexCoord + vec2(-1.0, 1.0) * texelSize).rgb);
+    float lumaSE = luma(texture(textureSampler, vTexCoord + vec2(1.0, 1.0) * texelSize).rgb);
+    
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+    
+    // blur along the edge, perpendicular to the luma gradient
+    vec2 dir = vec2(-((lumaNW + lumaNE) - (lumaSW + lumaSE)), (lumaNW + lumaSW) - (lumaNE + lumaSE));
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir * rcpDirMin, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * texelSize;
+    
+    vec3 rgbA = 0.5 * (texture(textureSampler, vTexCoord + dir * (1.0 / 3.0 - 0.5)).rgb
+        + texture(textureSampler, vTexCoord + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 rgbB = rgbA * 0.5 + 0.25 * (texture(textureSampler, vTexCoord - dir * 0.5).rgb
+        + texture(textureSampler, vTexCoord + dir * 0.5).rgb);
+    
+    // the wider blur crossed another edge, fall back to the narrow one
+    float lumaB = luma(rgbB);
+    fragColor = vec4((lumaB < lumaMin || lumaB > lumaMax) ? rgbA : rgbB, center.a);
+}
@@ -0,0 +1,72 @@
+#version 460 core
+
+// Writes the surface to the G-buffer: albedo and occlusion, world space normal, metallic and roughness.
+// Maps are enabled with ALBEDO_MAP, NORMAL_MAP, METALLIC_ROUGHNESS_MAP (roughness in G, metallic in B, as in
+// glTF) and OCCLUSION_MAP, and multiplied with the uniform values. Requires the lighting functions.
+
+in vec3 vWorldPos;
+in vec3 vNormal;
+in vec4 vColor;
+in vec2 vTexCoord;
+
+uniform vec4 albedo;
+uniform float metallic;
+uniform float roughness;
+// fragments with a lower alpha are discarded
+uniform float alphaCutoff;
+
+#ifdef ALBEDO_MAP
+uniform sampler2D albedoMap;
+#endif
+#ifdef NORMAL_MAP
+uniform sampler2D normalMap;
+#endif
+#ifdef METALLIC_ROUGHNESS_MAP
+uniform sampler2D metallicRoughnessMap;
+#endif
+#ifdef OCCLUSION_MAP
+uniform sampler2D occlusionMap;
+#endif
+
+layout(location = 0) out vec4 outAlbedo;
+layout(location = 1) out vec4 outNormal;
+layout(location = 2) out vec4 outMaterial;
+
+void main() {
+    vec4 base = albedo * vColor;
+#ifdef ALBEDO_MAP
+    base *= texture(albedoMap, vTexCoord);
+#endif
+    if (base.a < alphaCutoff) {
+        discard;
+    }
+    
+#ifdef NORMALS
+    vec3 N = normalize(vNormal);
+#else
+    vec3 N = normalize(cross(dFdx(vWorldPos), dFdy(vWorldPos)));
+#endif
+    if (!gl_FrontFacing) {
+        N = -N;
+    }
+#ifdef NORMAL_MAP
+    N = perturbNormal(N, vWorldPos, vTexCoord, texture(normalMap, vTexCoord).xyz * 2.0 - 1.0);
+#endif
+    
+    float surfaceMetallic = metallic;
+    float surfaceRoughness = roughness;
+#ifdef METALLIC_ROUGHNESS_MAP
+    vec4 metallicRoughness = texture(metallicRoughnessMap, vTexCoord);
+    surfaceRoughness *= metallicRoughness.g;
+    surfaceMetallic *= metallicRoughness.b;
+#endif
+    
+    float occlusion = 1.0;
+#ifdef OCCLUSION_MAP
+    occlusion = texture(occlusionMap, vTexCoord).r;
+#endif
+    
+    outAlbedo = vec4(base.rgb, occlusion);
+    outNormal = vec4(N, 0.0);
+    outMaterial = vec4(surfaceMetallic, surfaceRoughness, 0.0, 0.0);
+}
@@ -0,0 +1,41 @@
+#version 460 core
+
+// Shows one channel of the G-buffer. 0: albedo, 1: normals, 2: metallic, 3: roughness, 4: occlusion, 5: depth
+
+in vec2 vTexCoord;
+
+uniform sampler2D gAlbedo;
+uniform sampler2D gNormal;
+uniform sampler2D gMaterial;
+uniform sampler2D gDepth;
+uniform int channel;
+uniform float near;
+uniform float far;
+// depth is only linearized for perspective projections
+uniform bool perspective;
+
+out vec4 fragColor;
+
+void main() {
+    vec3 color;
+    if (channel == 0) {
+        color = texture(gAlbedo, vTexCoord).rgb;
+    } else if (channel == 1) {
+        color = texture(gNormal, vTexCoord).xyz * 0.5 + 0.5;
+    } else if (channel == 2) {
+        color = vec3(texture(gMaterial, vTexCoord).r);
+    } else if (channel == 3) {
+        color = vec3(texture(gMaterial, vTexCoord).g);
+    } else if (channel == 4) {
+        color = vec3(texture(gAlbedo, vTexCoord).a);
+    } else {
+        float depth = texture(gDepth, vTexCoord).r;
+        if (perspective) {
+            float z = depth * 2.0 - 1.0;
+            depth = (2.0 * near * far / (far + near - z * (far - near)) - near) / (far - near);
+        }
+        color = vec3(depth);
+    }
+    
+    fragColor = vec4(color, 1.0);
+}
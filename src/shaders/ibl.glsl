@@ -0,0 +1,27 @@
+// Image based ambient lighting from an `Environment`, set with `Environment::bind`. Requires the lighting functions.
+
+uniform samplerCube irradianceMap;
+uniform samplerCube prefilteredMap;
+uniform sampler2D brdfLut;
+// number of mip levels of prefilteredMap, from roughness 0 to 1
+uniform float prefilteredLevels;
+
+vec3 fresnelSchlickRoughness(float cosTheta, vec3 F0, float roughness) {
+    return F0 + (max(vec3(1.0 - roughness), F0) - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+// Diffuse and specular light reflected from the environment, before ambient occlusion
+vec3 ambientIBL(vec3 N, vec3 V, vec3 albedo, float metallic, float roughness) {
+    float NdotV = max(dot(N, V), 0.0);
+    vec3 F0 = mix(vec3(0.04), albedo, metallic);
+    vec3 F = fresnelSchlickRoughness(NdotV, F0, roughness);
+    
+    vec3 diffuse = texture(irradianceMap, N).rgb * albedo * (1.0 - F) * (1.0 - metallic);
+    
+    vec3 R = reflect(-V, N);
+    vec3 prefiltered = textureLod(prefilteredMap, R, roughness * (prefilteredLevels - 1.0)).rgb;
+    vec2 brdf = texture(brdfLut, vec2(NdotV, roughness)).rg;
+    vec3 specular = prefiltered * (F * brdf.x + brdf.y);
+    
+    return diffuse + specular;
+}
@@ -0,0 +1,42 @@
+#version 460 core
+
+// Split sum BRDF lookup table: scale (R) and bias (G) applied to F0, by N.V (x) and roughness (y). Requires the IBL
+// functions.
+
+in vec2 vTexCoord;
+
+uniform int sampleCount;
+
+out vec2 fragColor;
+
+float geometrySchlickIBL(float NdotX, float roughness) {
+    float k = roughness * roughness / 2.0;
+    
+    return NdotX / (NdotX * (1.0 - k) + k);
+}
+
+void main() {
+    float NdotV = max(vTexCoord.x, 0.001);
+    float roughness = vTexCoord.y;
+    vec3 V = vec3(sqrt(1.0 - NdotV * NdotV), 0.0, NdotV);
+    vec3 N = vec3(0.0, 0.0, 1.0);
+    
+    float scale = 0.0;
+    float bias = 0.0;
+    for (int i = 0; i < sampleCount; i++) {
+        vec3 H = importanceSampleGGX(hammersley(uint(i), uint(sampleCount)), N, roughness);
+        vec3 L = normalize(2.0 * dot(V, H) * H - V);
+        float NdotL = max(L.z, 0.0);
+        float NdotH = max(H.z, 0.0);
+        float VdotH = max(dot(V, H), 0.0);
+        if (NdotL > 0.0) {
+            float G = geometrySchlickIBL(NdotV, roughness) * geometrySchlickIBL(NdotL, roughness);
+            float visibility = G * VdotH / (NdotH * NdotV);
+            float fresnel = pow(1.0 - VdotH, 5.0);
+            scale += (1.0 - fresnel) * visibility;
+            bias += fresnel * visibility;
+        }
+    }
+    
+    fragColor = vec2(scale, bias) / float(sampleCount);
+}
@@ -0,0 +1,46 @@
+// Shared by the IBL precomputation passes, which render one cube map face at a time
+
+const float PI = 3.14159265359;
+
+// Direction through `uv` (0-1) of cube map face `face`, ordered +X, -X, +Y, -Y, +Z, -Z
+vec3 cubeDirection(int face, vec2 uv) {
+    float s = uv.x * 2.0 - 1.0;
+    float t = uv.y * 2.0 - 1.0;
+    vec3 dir;
+    if (face == 0) {
+        dir = vec3(1.0, -t, -s);
+    } else if (face == 1) {
+        dir = vec3(-1.0, -t, s);
+    } else if (face == 2) {
+        dir = vec3(s, 1.0, t);
+    } else if (face == 3) {
+        dir = vec3(s, -1.0, -t);
+    } else if (face == 4) {
+        dir = vec3(s, -t, 1.0);
+    } else {
+        dir = vec3(-s, -t, -1.0);
+    }
+    
+    return normalize(dir);
+}
+
+vec2 hammersley(uint i, uint count) {
+    uint bits = bitfieldReverse(i);
+    
+    return vec2(float(i) / float(count), float(bits) * 2.3283064365386963e-10);
+}
+
+// Half vector around `N` distributed according to GGX
+vec3 importanceSampleGGX(vec2 xi, vec3 N, float roughness) {
+    float a = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cosTheta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+    vec3 H = vec3(cos(phi) * sinTheta, sin(phi) * sinTheta, cosTheta);
+    
+    vec3 up = abs(N.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, N));
+    vec3 bitangent = cross(N, tangent);
+    
+    return normalize(tangent * H.x + bitangent * H.y + N * H.z);
+}
@@ -0,0 +1,17 @@
+#version 460 core
+
+// Samples an equirectangular panorama into one cube map face. Requires the IBL functions.
+
+in vec2 vTexCoord;
+
+uniform sampler2D panorama;
+uniform int face;
+
+out vec4 fragColor;
+
+void main() {
+    vec3 dir = cubeDirection(face, vTexCoord);
+    vec2 uv = vec2(0.5 + atan(dir.z, dir.x) / (2.0 * PI), 0.5 + asin(clamp(dir.y, -1.0, 1.0)) / PI);
+    
+    fragColor = vec4(texture(panorama, uv).rgb, 1.0);
+}
@@ -0,0 +1,33 @@
+#version 460 core
+
+// Cosine weighted convolution of the environment over the hemisphere, for diffuse lighting. Requires the IBL
+// functions.
+
+in vec2 vTexCoord;
+
+uniform samplerCube environment;
+uniform int face;
+
+out vec4 fragColor;
+
+void main() {
+    vec3 N = cubeDirection(face, vTexCoord);
+    vec3 up = abs(N.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+    vec3 right = normalize(cross(up, N));
+    up = cross(N, right);
+    
+    const float STEP = 0.025;
+    vec3 irradiance = vec3(0.0);
+    float count = 0.0;
+    for (float phi = 0.0; phi < 2.0 * PI; phi += STEP) {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += STEP) {
+            vec3 tangentDir = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 dir = tangentDir.x * right + tangentDir.y * up + tangentDir.z * N;
+            // a lower mip of the environment keeps the sampling noise down
+            irradiance += textureLod(environment, dir, 4.0).rgb * cos(theta) * sin(theta);
+            count += 1.0;
+        }
+    }
+    
+    fragColor = vec4(PI * irradiance / count, 1.0);
+}
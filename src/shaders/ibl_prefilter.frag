@@ -0,0 +1,44 @@
+#version 460 core
+
+// Convolves the environment with the GGX lobe of `roughness`, for one mip level of the specular map. Requires
+// the IBL functions.
+
+in vec2 vTexCoord;
+
+uniform samplerCube environment;
+uniform int face;
+uniform float roughness;
+uniform int sampleCount;
+// face size of the environment's base level
+uniform float environmentSize;
+
+out vec4 fragColor;
+
+void main() {
+    // assumes the view direction equals the normal, as the lobe's shape can't be stored per view angle
+    vec3 N = cubeDirection(face, vTexCoord);
+    vec3 V = N;
+    
+    vec3 color = vec3(0.0);
+    float weight = 0.0;
+    for (int i = 0; i < sampleCount; i++) {
+        vec3 H = importanceSampleGGX(hammersley(uint(i), uint(sampleCount)), N, roughness);
+        vec3 L = normalize(2.0 * dot(V, H) * H - V);
+        float NdotL = dot(N, L);
+        if (NdotL > 0.0) {
+            // sample a mip matching the solid angle covered by this sample, avoiding bright speckles
+            float NdotH = max(dot(N, H), 0.0);
+            float a2 = roughness * roughness * roughness * roughness;
+            float d = NdotH * NdotH * (a2 - 1.0) + 1.0;
+            float pdf = a2 / (PI * d * d) / 4.0 + 0.0001;
+            float texelSolidAngle = 4.0 * PI / (6.0 * environmentSize * environmentSize);
+            float sampleSolidAngle = 1.0 / (float(sampleCount) * pdf + 0.0001);
+            float level = roughness == 0.0 ? 0.0 : 0.5 * log2(sampleSolidAngle / texelSolidAngle);
+            
+            color += textureLod(environment, L, level).rgb * NdotL;
+            weight += NdotL;
+        }
+    }
+    
+    fragColor = vec4(color / weight, 1.0);
+}
@@ -0,0 +1,71 @@
+// Lighting functions shared by the lit shaders. `L` and `V` point from the surface towards the light and the
+// viewer, and like `N` are normalized.
+
+const float PI = 3.14159265359;
+
+// Close to inverse square falloff, smoothly reaching 0 at `range`
+float distanceAttenuation(float dist, float range) {
+    float ratio = dist / range;
+    float window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    
+    return window * window / (dist * dist + 1.0);
+}
+
+// `spotDirection` is the direction the light shines in, the cosines are of half the cone angles
+float spotAttenuation(vec3 L, vec3 spotDirection, float cosInner, float cosOuter) {
+    return smoothstep(cosOuter, cosInner, dot(-L, spotDirection));
+}
+
+float distributionGGX(float NdotH, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = NdotH * NdotH * (a2 - 1.0) + 1.0;
+    
+    return a2 / (PI * d * d);
+}
+
+float geometrySchlickGGX(float NdotX, float roughness) {
+    float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    
+    return NdotX / (NdotX * (1.0 - k) + k);
+}
+
+vec3 fresnelSchlick(float cosTheta, vec3 F0) {
+    return F0 + (1.0 - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+// Cook-Torrance GGX BRDF of the metallic-roughness model, multiplied by N.L. Multiply by the light's radiance.
+vec3 shadePBR(vec3 N, vec3 V, vec3 L, vec3 albedo, float metallic, float roughness) {
+    vec3 H = normalize(V + L);
+    float NdotL = max(dot(N, L), 0.0);
+    float NdotV = max(dot(N, V), 0.0001);
+    float NdotH = max(dot(N, H), 0.0);
+    roughness = clamp(roughness, 0.04, 1.0);
+    
+    vec3 F0 = mix(vec3(0.04), albedo, metallic);
+    vec3 F = fresnelSchlick(max(dot(H, V), 0.0), F0);
+    float D = distributionGGX(NdotH, roughness);
+    float G = geometrySchlickGGX(NdotV, roughness) * geometrySchlickGGX(NdotL, roughness);
+    
+    vec3 specular = D * G * F / max(4.0 * NdotV * NdotL, 0.0001);
+    vec3 diffuse = (1.0 - F) * (1.0 - metallic) * albedo / PI;
+    
+    return (diffuse + specular) * NdotL;
+}
+
+// Perturbs the interpolated normal `N` by a tangent space normal map sample (-1 to 1), using a tangent frame
+// derived from screen space derivatives, so meshes don't need tangents.
+vec3 perturbNormal(vec3 N, vec3 worldPos, vec2 uv, vec3 mapNormal) {
+    vec3 dp1 = dFdx(worldPos);
+    vec3 dp2 = dFdy(worldPos);
+    vec2 duv1 = dFdx(uv);
+    vec2 duv2 = dFdy(uv);
+    
+    vec3 dp2perp = cross(dp2, N);
+    vec3 dp1perp = cross(N, dp1);
+    vec3 T = dp2perp * duv1.x + dp1perp * duv2.x;
+    vec3 B = dp2perp * duv1.y + dp1perp * duv2.y;
+    float invMax = inversesqrt(max(dot(T, T), dot(B, B)));
+    
+    return normalize(mat3(T * invMax, B * invMax, N) * mapNormal);
+}
@@ -0,0 +1,40 @@
+// Lights uploaded by a `LightList`, and the camera position it sets. Requires the lighting functions.
+
+struct Light {
+    vec3 position;
+    // 0: directional, 1: point, 2: spot
+    int kind;
+    // direction the light shines in
+    vec3 direction;
+    float range;
+    // color times intensity
+    vec3 color;
+    float cosInner;
+    float cosOuter;
+};
+
+layout(std430) readonly buffer Lights {
+    Light lights[];
+};
+
+uniform int lightCount;
+uniform vec3 ambient;
+uniform vec3 cameraPosition;
+
+// Radiance arriving at `pos` from `light`, with the direction towards the light in `L`
+vec3 lightRadiance(Light light, vec3 pos, out vec3 L) {
+    if (light.kind == 0) {
+        L = -light.direction;
+        return light.color;
+    }
+    
+    vec3 toLight = light.position - pos;
+    float dist = length(toLight);
+    L = toLight / max(dist, 0.0001);
+    float attenuation = distanceAttenuation(dist, light.range);
+    if (light.kind == 2) {
+        attenuation *= spotAttenuation(L, light.direction, light.cosInner, light.cosOuter);
+    }
+    
+    return light.color * attenuation;
+}
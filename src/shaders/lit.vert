@@ -0,0 +1,37 @@
+#version 460 core
+
+// Lit meshes, for forward shading and the deferred geometry pass. Attributes missing from the mesh are replaced
+// by defaults unless VERTEX_COLORS, NORMALS and TEXTURED are defined.
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec4 color;
+layout(location = 2) in vec3 normal;
+layout(location = 3) in vec2 texCoord;
+
+uniform mat4 projModelView;
+uniform mat4 model;
+
+out vec3 vWorldPos;
+out vec3 vNormal;
+out vec4 vColor;
+out vec2 vTexCoord;
+
+void main() {
+    vWorldPos = (model * vec4(position, 1.0)).xyz;
+#ifdef NORMALS
+    vNormal = transpose(inverse(mat3(model))) * normal;
+#else
+    vNormal = vec3(0.0);
+#endif
+#ifdef VERTEX_COLORS
+    vColor = color;
+#else
+    vColor = vec4(1.0);
+#endif
+#ifdef TEXTURED
+    vTexCoord = texCoord;
+#else
+    vTexCoord = vec2(0.0);
+#endif
+    gl_Position = projModelView * vec4(position, 1.0);
+}
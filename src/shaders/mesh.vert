@@ -0,0 +1,13 @@
+#version 460 core
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec4 color;
+
+uniform mat4 projModelView;
+
+out vec4 vColor;
+
+void main() {
+    vColor = color;
+    gl_Position = projModelView * vec4(position, 1.0);
+}
@@ -0,0 +1,90 @@
+#version 460 core
+
+// Forward metallic-roughness shading, lit by a `LightList`. Maps are enabled with ALBEDO_MAP, NORMAL_MAP,
+// METALLIC_ROUGHNESS_MAP (roughness in G, metallic in B, as in glTF), OCCLUSION_MAP and EMISSIVE_MAP, and
+// multiplied with the uniform values. Requires the lighting functions and the light list, and the IBL functions
+// when IBL is defined to replace the ambient color with image based lighting. Outputs linear HDR color.
+
+in vec3 vWorldPos;
+in vec3 vNormal;
+in vec4 vColor;
+in vec2 vTexCoord;
+
+uniform vec4 albedo;
+uniform float metallic;
+uniform float roughness;
+uniform vec3 emissive;
+// fragments with a lower alpha are discarded
+uniform float alphaCutoff;
+
+#ifdef ALBEDO_MAP
+uniform sampler2D albedoMap;
+#endif
+#ifdef NORMAL_MAP
+uniform sampler2D normalMap;
+#endif
+#ifdef METALLIC_ROUGHNESS_MAP
+uniform sampler2D metallicRoughnessMap;
+#endif
+#ifdef OCCLUSION_MAP
+uniform sampler2D occlusionMap;
+#endif
+#ifdef EMISSIVE_MAP
+uniform sampler2D emissiveMap;
+#endif
+
+out vec4 fragColor;
+
+void main() {
+    vec4 base = albedo * vColor;
+#ifdef ALBEDO_MAP
+    base *= texture(albedoMap, vTexCoord);
+#endif
+    if (base.a < alphaCutoff) {
+        discard;
+    }
+    
+#ifdef NORMALS
+    vec3 N = normalize(vNormal);
+#else
+    vec3 N = normalize(cross(dFdx(vWorldPos), dFdy(vWorldPos)));
+#endif
+    if (!gl_FrontFacing) {
+        N = -N;
+    }
+#ifdef NORMAL_MAP
+    N = perturbNormal(N, vWorldPos, vTexCoord, texture(normalMap, vTexCoord).xyz * 2.0 - 1.0);
+#endif
+    
+    float surfaceMetallic = metallic;
+    float surfaceRoughness = roughness;
+#ifdef METALLIC_ROUGHNESS_MAP
+    vec4 metallicRoughness = texture(metallicRoughnessMap, vTexCoord);
+    surfaceRoughness *= metallicRoughness.g;
+    surfaceMetallic *= metallicRoughness.b;
+#endif
+    
+    float occlusion = 1.0;
+#ifdef OCCLUSION_MAP
+    occlusion = texture(occlusionMap, vTexCoord).r;
+#endif
+    
+    vec3 V = normalize(cameraPosition - vWorldPos);
+#ifdef IBL
+    vec3 color = ambientIBL(N, V, base.rgb, surfaceMetallic, surfaceRoughness) * occlusion;
+#else
+    vec3 color = ambient * base.rgb * occlusion;
+#endif
+    for (int i = 0; i < lightCount; i++) {
+        vec3 L;
+        vec3 radiance = lightRadiance(lights[i], vWorldPos, L);
+        color += shadePBR(N, V, L, base.rgb, surfaceMetallic, surfaceRoughness) * radiance;
+    }
+    
+    vec3 emission = emissive;
+#ifdef EMISSIVE_MAP
+    emission *= texture(emissiveMap, vTexCoord).rgb;
+#endif
+    
+    fragColor = vec4(color + emission, base.a);
+}
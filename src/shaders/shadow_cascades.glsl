@@ -0,0 +1,35 @@
+// Cascaded shadow map of a `DirectionalShadow`, set with `DirectionalShadow::bind`. Requires the PCF functions.
+
+#ifndef SHADOW_MAX_CASCADES
+#define SHADOW_MAX_CASCADES 4
+#endif
+
+uniform sampler2DArrayShadow shadowMap;
+// world space to shadow map texture space, per cascade
+uniform mat4 shadowMatrices[SHADOW_MAX_CASCADES];
+// far end of each cascade, as a distance along the camera direction
+uniform float cascadeSplits[SHADOW_MAX_CASCADES];
+uniform int cascadeCount;
+
+int shadowCascade(float viewDepth) {
+    for (int i = 0; i < cascadeCount - 1; i++) {
+        if (viewDepth < cascadeSplits[i]) {
+            return i;
+        }
+    }
+    
+    return cascadeCount - 1;
+}
+
+// 1 where `worldPos` is lit by the light, 0 where it's shadowed. `viewDepth` is the distance along the camera
+// direction, i.e. -(view * worldPos).z. The bias grows with each cascade, as their texels cover more area.
+float directionalShadow(vec3 worldPos, float viewDepth, float bias) {
+    if (cascadeCount == 0 || viewDepth > cascadeSplits[cascadeCount - 1]) {
+        return 1.0;
+    }
+    
+    int cascade = shadowCascade(viewDepth);
+    vec4 coord = shadowMatrices[cascade] * vec4(worldPos, 1.0);
+    
+    return pcfArray(shadowMap, coord.xyz / coord.w, float(cascade), bias * float(cascade + 1));
+}
@@ -0,0 +1,6 @@
+#version 460 core
+
+// Depth only, the depth buffer is written by the fixed function pipeline
+
+void main() {
+}
@@ -0,0 +1,9 @@
+#version 460 core
+
+layout(location = 0) in vec3 position;
+
+uniform mat4 projModelView;
+
+void main() {
+    gl_Position = projModelView * vec4(position, 1.0);
+}
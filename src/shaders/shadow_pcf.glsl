@@ -0,0 +1,31 @@
+// Percentage closer filtering over a (2 * SHADOW_PCF_RADIUS + 1)^2 texel grid, with hardware 2x2 filtering
+// for each tap. `coord` is in shadow map texture space: 0-1 coordinates and depth.
+// Both return 1 where lit and 0 where fully shadowed.
+
+#ifndef SHADOW_PCF_RADIUS
+#define SHADOW_PCF_RADIUS 1
+#endif
+
+float pcf2D(sampler2DShadow map, vec3 coord, float bias) {
+    vec2 texel = 1.0 / vec2(textureSize(map, 0));
+    float lit = 0.0;
+    for (int x = -SHADOW_PCF_RADIUS; x <= SHADOW_PCF_RADIUS; x++) {
+        for (int y = -SHADOW_PCF_RADIUS; y <= SHADOW_PCF_RADIUS; y++) {
+            lit += texture(map, vec3(coord.xy + vec2(x, y) * texel, coord.z - bias));
+        }
+    }
+    
+    return lit / float((2 * SHADOW_PCF_RADIUS + 1) * (2 * SHADOW_PCF_RADIUS + 1));
+}
+
+float pcfArray(sampler2DArrayShadow map, vec3 coord, float layer, float bias) {
+    vec2 texel = 1.0 / vec2(textureSize(map, 0).xy);
+    float lit = 0.0;
+    for (int x = -SHADOW_PCF_RADIUS; x <= SHADOW_PCF_RADIUS; x++) {
+        for (int y = -SHADOW_PCF_RADIUS; y <= SHADOW_PCF_RADIUS; y++) {
+            lit += texture(map, vec4(coord.xy + vec2(x, y) * texel, layer, coord.z - bias));
+        }
+    }
+    
+    return lit / float((2 * SHADOW_PCF_RADIUS + 1) * (2 * SHADOW_PCF_RADIUS + 1));
+}
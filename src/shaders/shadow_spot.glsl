@@ -0,0 +1,19 @@
+// Shadow map of a `SpotShadow`, set with `SpotShadow::bind`. Requires the PCF functions.
+
+uniform sampler2DShadow spotShadowMap;
+// world space to shadow map texture space
+uniform mat4 spotShadowMatrix;
+
+// 1 where `worldPos` is lit by the light, 0 where it's shadowed. Points outside the cone are considered lit.
+float spotShadow(vec3 worldPos, float bias) {
+    vec4 coord = spotShadowMatrix * vec4(worldPos, 1.0);
+    if (coord.w <= 0.0) {
+        return 1.0;
+    }
+    coord.xyz /= coord.w;
+    if (coord.z > 1.0) {
+        return 1.0;
+    }
+    
+    return pcf2D(spotShadowMap, coord.xyz, bias);
+}
@@ -0,0 +1,150 @@
+#version 460 core
+
+// Sky color by view direction, from a cube map, an equirectangular panorama, a gradient or single scattering
+// through an atmosphere, chosen by skyMode. Outputs linear HDR color.
+
+in vec3 vDirection;
+
+// 0: cube map, 1: equirectangular, 2: gradient, 3: atmosphere
+uniform int skyMode;
+uniform float intensity;
+
+uniform samplerCube skyCube;
+uniform sampler2D skyPanorama;
+
+// towards the sun
+uniform vec3 sunDirection;
+// angular diameter in degrees
+uniform float sunSize;
+
+uniform vec3 zenithColor;
+uniform vec3 horizonColor;
+uniform vec3 groundColor;
+uniform vec3 sunColor;
+
+// scattering coefficients per meter
+uniform vec3 rayleigh;
+uniform float mie;
+// mie anisotropy, towards 1 for a stronger halo around the sun
+uniform float mieG;
+uniform float sunIntensity;
+
+out vec4 fragColor;
+
+const float PI = 3.14159265359;
+const float PLANET_RADIUS = 6371e3;
+const float ATMOSPHERE_RADIUS = 6471e3;
+const float RAYLEIGH_HEIGHT = 8e3;
+const float MIE_HEIGHT = 1.2e3;
+const int VIEW_STEPS = 16;
+const int LIGHT_STEPS = 8;
+
+float sunDisk(vec3 dir) {
+    float cosRadius = cos(radians(sunSize * 0.5));
+    
+    return smoothstep(cosRadius - 0.00002, cosRadius + 0.00002, dot(dir, sunDirection));
+}
+
+vec3 gradient(vec3 dir) {
+    vec3 color;
+    if (dir.y >= 0.0) {
+        color = mix(horizonColor, zenithColor, pow(dir.y, 0.5));
+    } else {
+        color = mix(horizonColor, groundColor, pow(-dir.y, 0.3));
+    }
+    
+    float glow = pow(max(dot(dir, sunDirection), 0.0), 64.0) * 0.5;
+    float aboveHorizon = smoothstep(-0.01, 0.01, dir.y);
+    
+    return color + sunColor * (glow + sunDisk(dir)) * aboveHorizon;
+}
+
+// Distances to the near and far intersections with a sphere around the planet's center, far < near on a miss
+vec2 raySphere(vec3 origin, vec3 dir, float radius) {
+    float b = dot(origin, dir);
+    float c = dot(origin, origin) - radius * radius;
+    float d = b * b - c;
+    if (d < 0.0) {
+        return vec2(1e9, -1e9);
+    }
+    d = sqrt(d);
+    
+    return vec2(-b - d, -b + d);
+}
+
+vec3 atmosphere(vec3 dir) {
+    vec3 origin = vec3(0.0, PLANET_RADIUS + 1.0, 0.0);
+    float len = raySphere(origin, dir, ATMOSPHERE_RADIUS).y;
+    vec2 ground = raySphere(origin, dir, PLANET_RADIUS);
+    bool hitsGround = ground.x > 0.0 && ground.y > ground.x;
+    if (hitsGround) {
+        len = ground.x;
+    }
+    
+    float mu = dot(dir, sunDirection);
+    float g2 = mieG * mieG;
+    float rayleighPhase = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+    float miePhase = 3.0 / (8.0 * PI) * ((1.0 - g2) * (1.0 + mu * mu)) / ((2.0 + g2) * pow(1.0 + g2 - 2.0 * mieG * mu, 1.5));
+    
+    float stepSize = len / float(VIEW_STEPS);
+    float rayleighDepth = 0.0;
+    float mieDepth = 0.0;
+    vec3 rayleighSum = vec3(0.0);
+    vec3 mieSum = vec3(0.0);
+    for (int i = 0; i < VIEW_STEPS; i++) {
+        vec3 pos = origin + dir * (float(i) + 0.5) * stepSize;
+        float height = length(pos) - PLANET_RADIUS;
+        float rayleighStep = exp(-height / RAYLEIGH_HEIGHT) * stepSize;
+        float mieStep = exp(-height / MIE_HEIGHT) * stepSize;
+        rayleighDepth += rayleighStep;
+        mieDepth += mieStep;
+        
+        float lightStepSize = raySphere(pos, sunDirection, ATMOSPHERE_RADIUS).y / float(LIGHT_STEPS);
+        float lightRayleigh = 0.0;
+        float lightMie = 0.0;
+        for (int j = 0; j < LIGHT_STEPS; j++) {
+            vec3 lightPos = pos + sunDirection * (float(j) + 0.5) * lightStepSize;
+            float lightHeight = length(lightPos) - PLANET_RADIUS;
+            lightRayleigh += exp(-lightHeight / RAYLEIGH_HEIGHT) * lightStepSize;
+            lightMie += exp(-lightHeight / MIE_HEIGHT) * lightStepSize;
+        }
+        
+        vec3 attenuation = exp(-(rayleigh * (rayleighDepth + lightRayleigh) + mie * 1.1 * (mieDepth + lightMie)));
+        rayleighSum += rayleighStep * attenuation;
+        mieSum += mieStep * attenuation;
+    }
+    
+    vec3 color = sunIntensity * (rayleighPhase * rayleigh * rayleighSum + miePhase * mie * mieSum);
+    if (!hitsGround) {
+        vec3 transmittance = exp(-(rayleigh * rayleighDepth + mie * 1.1 * mieDepth));
+        color += sunIntensity * transmittance * sunDisk(dir);
+    }
+    
+    return color;
+}
+
+void main() {
+    vec3 dir = normalize(vDirection);
+    
+    vec3 color;
+    if (skyMode == 0) {
+        color = texture(skyCube, dir).rgb;
+    } else if (skyMode == 1) {
+        // textures are stored bottom row first, so the top of the panorama is at v = 1
+        vec2 uv = vec2(0.5 + atan(dir.z, dir.x) / (2.0 * PI), 0.5 + asin(clamp(dir.y, -1.0, 1.0)) / PI);
+        // u wraps around at -X, where the derivatives would jump and pick the smallest mip, so use those of a
+        // panorama wrapping at +X wherever they're smaller
+        vec2 seamUv = vec2(fract(uv.x + 0.5), uv.y);
+        vec2 dx = dFdx(uv);
+        vec2 dy = dFdy(uv);
+        dx.x = min(abs(dx.x), abs(dFdx(seamUv.x)));
+        dy.x = min(abs(dy.x), abs(dFdy(seamUv.x)));
+        color = textureGrad(skyPanorama, uv, dx, dy).rgb;
+    } else if (skyMode == 2) {
+        color = gradient(dir);
+    } else {
+        color = atmosphere(dir);
+    }
+    
+    fragColor = vec4(color * intensity, 1.0);
+}
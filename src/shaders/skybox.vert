@@ -0,0 +1,17 @@
+#version 460 core
+
+// Draws a triangle covering the viewport at the far plane, passing the view direction of each corner. Use with
+// glDrawArrays(GL_TRIANGLES, 0, 3) and no vertex buffers.
+
+// inverse of the camera's projection * view, with the view's translation removed
+uniform mat4 invViewProj;
+
+out vec3 vDirection;
+
+void main() {
+    vec2 pos = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2) * 2.0 - 1.0;
+    vec4 far = invViewProj * vec4(pos, 1.0, 1.0);
+    vDirection = far.xyz / far.w;
+    // z = w puts the sky at depth 1.0, behind everything drawn with DepthFunc(LEQUAL)
+    gl_Position = vec4(pos, 1.0, 1.0);
+}
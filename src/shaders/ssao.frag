@@ -0,0 +1,60 @@
+#version 460 core
+
+// Screen space ambient occlusion: samples a hemisphere around each pixel's normal, in view space, and counts the
+// samples hidden behind the depth buffer. Outputs 1 for unoccluded pixels, down to 0.
+
+in vec2 vTexCoord;
+
+uniform sampler2D depthMap;
+// world space normals
+uniform sampler2D normalMap;
+// random rotations around the normal (RG), tiled over the screen
+uniform sampler2D noiseMap;
+uniform vec2 noiseScale;
+
+uniform mat4 projection;
+uniform mat4 invProjection;
+uniform mat4 view;
+
+// hemisphere sample offsets, closer to the center towards the start
+uniform vec3 kernel[64];
+uniform int sampleCount;
+uniform float radius;
+uniform float bias;
+uniform float power;
+
+out float fragColor;
+
+vec3 viewPosition(vec2 uv) {
+    float depth = texture(depthMap, uv).r;
+    vec4 pos = invProjection * vec4(uv * 2.0 - 1.0, depth * 2.0 - 1.0, 1.0);
+    
+    return pos.xyz / pos.w;
+}
+
+void main() {
+    if (texture(depthMap, vTexCoord).r == 1.0) {
+        fragColor = 1.0;
+        return;
+    }
+    
+    vec3 pos = viewPosition(vTexCoord);
+    vec3 N = normalize(mat3(view) * texture(normalMap, vTexCoord).xyz);
+    vec3 random = vec3(texture(noiseMap, vTexCoord * noiseScale).xy, 0.0);
+    vec3 T = normalize(random - N * dot(random, N));
+    mat3 TBN = mat3(T, cross(N, T), N);
+    
+    float occlusion = 0.0;
+    for (int i = 0; i < sampleCount; i++) {
+        vec3 samplePos = pos + TBN * kernel[i] * radius;
+        vec4 clip = projection * vec4(samplePos, 1.0);
+        vec2 uv = clip.xy / clip.w * 0.5 + 0.5;
+        float sceneDepth = viewPosition(uv).z;
+        
+        // surfaces much closer to the camera than the sample shouldn't darken it
+        float range = smoothstep(0.0, 1.0, radius / abs(pos.z - sceneDepth));
+        occlusion += (sceneDepth >= samplePos.z + bias ? 1.0 : 0.0) * range;
+    }
+    
+    fragColor = pow(1.0 - occlusion / float(sampleCount), power);
+}
@@ -0,0 +1,41 @@
+#version 460 core
+
+// One direction of a depth aware (bilateral) gaussian blur of the occlusion, smoothing out the noise pattern
+// without bleeding across edges.
+
+in vec2 vTexCoord;
+
+uniform sampler2D occlusionMap;
+uniform sampler2D depthMap;
+uniform mat4 invProjection;
+// one texel along the blur direction
+uniform vec2 direction;
+// how quickly depth differences stop samples from contributing
+uniform float sharpness;
+
+out float fragColor;
+
+const int RADIUS = 4;
+
+float viewDepth(vec2 uv) {
+    float depth = texture(depthMap, uv).r;
+    vec4 pos = invProjection * vec4(uv * 2.0 - 1.0, depth * 2.0 - 1.0, 1.0);
+    
+    return pos.z / pos.w;
+}
+
+void main() {
+    float center = viewDepth(vTexCoord);
+    
+    float sum = 0.0;
+    float weights = 0.0;
+    for (int i = -RADIUS; i <= RADIUS; i++) {
+        vec2 uv = vTexCoord + direction * float(i);
+        float difference = (viewDepth(uv) - center) / center;
+        float weight = exp(-float(i * i) / float(RADIUS * RADIUS)) * exp(-difference * difference * sharpness);
+        sum += texture(occlusionMap, uv).r * weight;
+        weights += weight;
+    }
+    
+    fragColor = sum / weights;
+}
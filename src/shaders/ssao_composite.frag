@@ -0,0 +1,16 @@
+#version 460 core
+
+// Outputs the occlusion as a color, to multiply the lighting result with blending.
+
+in vec2 vTexCoord;
+
+uniform sampler2D occlusionMap;
+uniform float strength;
+
+out vec4 fragColor;
+
+void main() {
+    float occlusion = mix(1.0, texture(occlusionMap, vTexCoord).r, strength);
+    
+    fragColor = vec4(vec3(occlusion), 1.0);
+}
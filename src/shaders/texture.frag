@@ -0,0 +1,12 @@
+#version 460 core
+
+in vec4 vColor;
+in vec2 vTexCoord;
+
+uniform sampler2D textureSampler;
+
+out vec4 fragColor;
+
+void main() {
+    fragColor = texture(textureSampler, vTexCoord) * vColor;
+}
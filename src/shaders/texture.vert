@@ -0,0 +1,16 @@
+#version 460 core
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec4 color;
+layout(location = 3) in vec2 texCoord;
+
+uniform mat4 projModelView;
+
+out vec4 vColor;
+out vec2 vTexCoord;
+
+void main() {
+    vColor = color;
+    vTexCoord = texCoord;
+    gl_Position = projModelView * vec4(position, 1.0);
+}
@@ -0,0 +1,10 @@
+#version 460 core
+
+uniform sampler2D textureSampler;
+uniform vec4 tint;
+
+out vec4 fragColor;
+
+void main() {
+    fragColor = texelFetch(textureSampler, ivec2(gl_FragCoord.xy), 0) * tint;
+}
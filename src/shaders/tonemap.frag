@@ -0,0 +1,62 @@
+#version 460 core
+
+// Maps HDR colors to the 0-1 range, then applies gamma correction (gamma 1 leaves the output linear, for sRGB framebuffers).
+
+in vec2 vTexCoord;
+
+uniform sampler2D textureSampler;
+uniform float exposure;
+// 0: Reinhard, 1: ACES, 2: filmic
+uniform int toneMapOperator;
+uniform float gamma;
+
+out vec4 fragColor;
+
+vec3 reinhard(vec3 x) {
+    return x / (1.0 + x);
+}
+
+// Krzysztof Narkowicz's fit of the ACES reference curve
+vec3 aces(vec3 x) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+
+// John Hable's Uncharted 2 curve
+vec3 hable(vec3 x) {
+    const float A = 0.15;
+    const float B = 0.50;
+    const float C = 0.10;
+    const float D = 0.20;
+    const float E = 0.02;
+    const float F = 0.30;
+    
+    return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
+
+vec3 filmic(vec3 x) {
+    const float WHITE_POINT = 11.2;
+    
+    return hable(x * 2.0) / hable(vec3(WHITE_POINT));
+}
+
+void main() {
+    vec4 color = texture(textureSampler, vTexCoord);
+    vec3 hdr = color.rgb * exposure;
+    
+    vec3 mapped;
+    if (toneMapOperator == 1) {
+        mapped = aces(hdr);
+    } else if (toneMapOperator == 2) {
+        mapped = filmic(hdr);
+    } else {
+        mapped = reinhard(hdr);
+    }
+    
+    fragColor = vec4(pow(mapped, vec3(1.0 / gamma)), color.a);
+}
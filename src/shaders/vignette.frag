@@ -0,0 +1,19 @@
+#version 460 core
+
+in vec2 vTexCoord;
+
+uniform sampler2D textureSampler;
+uniform float intensity;
+// Distance from the center (1 at the corners) where darkening ends, and the width of the falloff
+uniform float radius;
+uniform float smoothness;
+
+out vec4 fragColor;
+
+void main() {
+    vec4 color = texture(textureSampler, vTexCoord);
+    float dist = length(vTexCoord - 0.5) * 1.41421356;
+    float vignette = smoothstep(radius, radius - smoothness, dist);
+    
+    fragColor = vec4(color.rgb * mix(1.0, vignette, intensity), color.a);
+}
@@ -0,0 +1,382 @@
+use cgmath::{ortho, perspective, vec3, Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, Vector3, Vector4};
+use gl::types::*;
+use crate::camera::Camera;
+use crate::framebuffer::{Attachment, Framebuffer};
+use crate::graphics::{CompareFunc, GlTexture, ShaderProgram, Texture, TextureFormat, TextureParams, Wrap};
+use crate::shaders;
+use crate::texture::Texture2DArray;
+
+/// Most cascades a `DirectionalShadow` can have, `SHADOW_MAX_CASCADES` in `shaders::SHADOW_CASCADES_GLSL`
+pub const MAX_CASCADES: usize = 4;
+
+/// Depth textures sampled with a shadow sampler: hardware 2x2 PCF, and everything outside the map is lit.
+fn shadow_params() -> TextureParams {
+    TextureParams::linear()
+        .with_wrap(Wrap::ClampToBorder)
+        .with_border_color(1.0, 1.0, 1.0, 1.0)
+        .with_compare(CompareFunc::LessEqual)
+}
+
+fn depth_shader() -> ShaderProgram {
+    let mut shader = ShaderProgram::new();
+    shader.create_vertex_shader(shaders::SHADOW_DEPTH_VERT);
+    shader.create_fragment_shader(shaders::SHADOW_DEPTH_FRAG);
+    shader.link();
+    
+    shader
+}
+
+/// Maps clip space to shadow map texture space (0-1 coordinates and depth)
+fn texture_space() -> Matrix4<f32> {
+    Matrix4::from_translation(vec3(0.5, 0.5, 0.5)) * Matrix4::from_scale(0.5)
+}
+
+/// An up vector that isn't parallel to `direction`
+fn light_up(direction: Vector3<f32>) -> Vector3<f32> {
+    if direction.normalize().y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    }
+}
+
+/// GL state changed while rendering a shadow map
+struct DepthPass {
+    depth_test: bool,
+    depth_clamp: bool,
+    polygon_offset: bool,
+}
+impl DepthPass {
+    fn begin(offset: (f32, f32), clamp: bool) -> Self {
+        unsafe {
+            let depth_test = gl::IsEnabled(gl::DEPTH_TEST) == gl::TRUE;
+            let depth_clamp = gl::IsEnabled(gl::DEPTH_CLAMP) == gl::TRUE;
+            let polygon_offset = gl::IsEnabled(gl::POLYGON_OFFSET_FILL) == gl::TRUE;
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthMask(gl::TRUE);
+            gl::Enable(gl::POLYGON_OFFSET_FILL);
+            gl::PolygonOffset(offset.0, offset.1);
+            if clamp {
+                gl::Enable(gl::DEPTH_CLAMP);
+            }
+            
+            Self { depth_test, depth_clamp, polygon_offset }
+        }
+    }
+    
+    fn end(self) {
+        unsafe {
+            if !self.depth_test {
+                gl::Disable(gl::DEPTH_TEST);
+            }
+            if !self.polygon_offset {
+                gl::Disable(gl::POLYGON_OFFSET_FILL);
+            }
+            if !self.depth_clamp {
+                gl::Disable(gl::DEPTH_CLAMP);
+            }
+        }
+    }
+}
+
+/// Cascaded shadow maps for a directional light, such as the sun.
+///
+/// The camera's view range is split into `cascades` slices, each covered by its own layer of a depth texture
+/// array, so nearby shadows get more resolution than distant ones. Every frame, call `update` with the camera,
+/// then `render` to draw the shadow casters into each cascade, then `bind` the maps to the shader that receives
+/// the shadows. That shader includes `shaders::SHADOW_PCF_GLSL` and `shaders::SHADOW_CASCADES_GLSL` and calls
+/// `directionalShadow`.
+pub struct DirectionalShadow {
+    /// Direction the light shines in
+    pub direction: Vector3<f32>,
+    /// Blend between logarithmic (1) and uniform (0) cascade splits, 0.75 by default
+    pub split_lambda: f32,
+    /// Shadows end this far from the camera (or at its far plane if closer), 100 by default
+    pub max_distance: f32,
+    /// Slope scaled and constant depth offset applied while rendering, see `glPolygonOffset`. (2, 4) by default.
+    pub depth_offset: (f32, f32),
+    map: Texture2DArray,
+    fbo: GLuint,
+    shader: ShaderProgram,
+    matrices: Vec<Matrix4<f32>>,
+    splits: Vec<f32>,
+}
+impl DirectionalShadow {
+    /// Creates `cascades` shadow maps of `resolution`x`resolution` texels. Panics if `cascades` is 0 or more
+    /// than `MAX_CASCADES`.
+    pub fn new(direction: Vector3<f32>, resolution: u32, cascades: usize) -> Self {
+        if cascades == 0 || cascades > MAX_CASCADES {
+            panic!("Directional shadows need 1 to {} cascades, {} requested", MAX_CASCADES, cascades);
+        }
+        
+        let map = Texture2DArray::empty(resolution, resolution, cascades as u32, TextureFormat::Depth32F, shadow_params());
+        let mut fbo = 0;
+        unsafe {
+            gl::CreateFramebuffers(1, &mut fbo);
+            gl::NamedFramebufferDrawBuffer(fbo, gl::NONE);
+            gl::NamedFramebufferReadBuffer(fbo, gl::NONE);
+        }
+        
+        Self {
+            direction: direction.normalize(),
+            split_lambda: 0.75,
+            max_distance: 100.0,
+            depth_offset: (2.0, 4.0),
+            map,
+            fbo,
+            shader: depth_shader(),
+            matrices: Vec::new(),
+            splits: Vec::new(),
+        }
+    }
+    
+    pub fn with_split_lambda(mut self, split_lambda: f32) -> Self {
+        self.split_lambda = split_lambda;
+        
+        self
+    }
+    
+    pub fn with_max_distance(mut self, max_distance: f32) -> Self {
+        self.max_distance = max_distance;
+        
+        self
+    }
+    
+    /// Fits each cascade to its slice of `camera`'s view. Cascades are snapped to whole texels, so shadows
+    /// don't shimmer when the camera moves.
+    pub fn update(&mut self, camera: &Camera) {
+        let cascades = self.map.layers as usize;
+        let near = camera.near;
+        let far = camera.far.min(self.max_distance);
+        
+        self.splits.clear();
+        for i in 1..=cascades {
+            let p = i as f32 / cascades as f32;
+            let uniform = near + (far - near) * p;
+            // logarithmic splits need a positive near plane, orthographic cameras may not have one
+            let split = if camera.is_perspective && near > 0.0 {
+                let log = near * (far / near).powf(p);
+                self.split_lambda * log + (1.0 - self.split_lambda) * uniform
+            } else {
+                uniform
+            };
+            self.splits.push(split);
+        }
+        
+        self.matrices.clear();
+        let mut start = near;
+        for i in 0..cascades {
+            let corners = slice_corners(camera, start, self.splits[i]);
+            self.matrices.push(self.fit(&corners));
+            start = self.splits[i];
+        }
+    }
+    
+    /// Orthographic light projection covering the bounding sphere of `corners`, which doesn't change size as
+    /// the camera rotates.
+    fn fit(&self, corners: &[Vector3<f32>; 8]) -> Matrix4<f32> {
+        let center = corners.iter().fold(Vector3::new(0.0, 0.0, 0.0), |sum, corner| sum + corner) / 8.0;
+        let radius = corners.iter().map(|corner| (corner - center).magnitude()).fold(0.0, f32::max);
+        let radius = (radius * 16.0).ceil() / 16.0;
+        
+        let direction = self.direction.normalize();
+        let eye = center - direction * radius;
+        let view = Matrix4::look_at_rh(Point3::from_vec(eye), Point3::from_vec(center), light_up(direction));
+        let mut projection = ortho(-radius, radius, -radius, radius, 0.0, radius * 2.0);
+        
+        // move the projection by less than a texel so the world origin lands on a texel corner
+        let half = self.map.width as f32 / 2.0;
+        let origin = (projection * view) * Vector4::new(0.0, 0.0, 0.0, 1.0);
+        projection.w.x += ((origin.x * half).round() - origin.x * half) / half;
+        projection.w.y += ((origin.y * half).round() - origin.y * half) / half;
+        
+        projection * view
+    }
+    
+    /// Renders every cascade. `draw` is called once per cascade with the bound depth shader and the light's
+    /// view-projection matrix; multiply it by each caster's model matrix and render with the shader bound
+    /// externally, e.g. `mesh.render(shader, true, gl::TRIANGLES, light * model)`. Casters between the
+    /// light and a cascade are clamped to its near plane, so they still cast shadows.
+    pub fn render(&self, mut draw: impl FnMut(&ShaderProgram, Matrix4<f32>)) {
+        let mut previous = 0;
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut previous);
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::Viewport(0, 0, self.map.width as GLsizei, self.map.height as GLsizei);
+        }
+        let pass = DepthPass::begin(self.depth_offset, true);
+        
+        self.shader.bind();
+        for (layer, matrix) in self.matrices.iter().enumerate() {
+            unsafe {
+                gl::NamedFramebufferTextureLayer(self.fbo, gl::DEPTH_ATTACHMENT, self.map.id(), 0, layer as GLint);
+                gl::ClearNamedFramebufferfv(self.fbo, gl::DEPTH, 0, &1.0);
+            }
+            draw(&self.shader, *matrix);
+        }
+        self.shader.unbind();
+        
+        pass.end();
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous as GLuint);
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+    }
+    
+    /// Binds the shadow maps to texture unit `unit` and sets the uniforms of `shaders::SHADOW_CASCADES_GLSL`.
+    /// `shader` must be bound.
+    pub fn bind(&self, shader: &ShaderProgram, unit: u32) {
+        self.map.bind_to(unit);
+        shader.set_uniform1i32("shadowMap", unit as i32);
+        shader.set_uniform1i32("cascadeCount", self.matrices.len() as i32);
+        for (i, (matrix, split)) in self.matrices.iter().zip(&self.splits).enumerate() {
+            shader.set_uniform_mat4f(&format!("shadowMatrices[{}]", i), texture_space() * matrix);
+            shader.set_uniform1f32(&format!("cascadeSplits[{}]", i), *split);
+        }
+    }
+    
+    /// View-projection matrix of each cascade's light, from world space to the cascade's clip space
+    pub fn matrices(&self) -> &[Matrix4<f32>] {
+        &self.matrices
+    }
+    
+    /// Far end of each cascade, as a distance along the camera direction
+    pub fn splits(&self) -> &[f32] {
+        &self.splits
+    }
+    
+    /// Depth texture array with one layer per cascade, sampled with depth comparison
+    pub fn depth_texture(&self) -> &Texture2DArray {
+        &self.map
+    }
+    
+    pub fn cascades(&self) -> usize {
+        self.map.layers as usize
+    }
+}
+impl Drop for DirectionalShadow {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+        }
+    }
+}
+
+/// World space corners of the part of `camera`'s view between distances `near` and `far` along its direction.
+fn slice_corners(camera: &Camera, near: f32, far: f32) -> [Vector3<f32>; 8] {
+    let direction = camera.direction.normalize();
+    let right = direction.cross(camera.up).normalize();
+    let up = right.cross(direction);
+    
+    let half_size = |distance: f32| {
+        if camera.is_perspective {
+            let half_height = distance * (Rad::from(Deg(camera.fov)).0 * 0.5).tan();
+            (half_height * camera.aspect, half_height)
+        } else {
+            (camera.viewport_width / 2.0, camera.viewport_height / 2.0)
+        }
+    };
+    
+    let mut corners = [Vector3::new(0.0, 0.0, 0.0); 8];
+    for (i, distance) in [near, far].into_iter().enumerate() {
+        let center = camera.position + direction * distance;
+        let (width, height) = half_size(distance);
+        corners[i * 4] = center - right * width - up * height;
+        corners[i * 4 + 1] = center + right * width - up * height;
+        corners[i * 4 + 2] = center + right * width + up * height;
+        corners[i * 4 + 3] = center - right * width + up * height;
+    }
+    
+    corners
+}
+
+/// Shadow map for a spot light, rendered with a perspective projection matching its cone.
+///
+/// Set the light's fields, call `update`, then `render` the shadow casters and `bind` the map to the shader
+/// that receives the shadows. That shader includes `shaders::SHADOW_PCF_GLSL` and `shaders::SHADOW_SPOT_GLSL`
+/// and calls `spotShadow`.
+pub struct SpotShadow {
+    pub position: Vector3<f32>,
+    /// Direction the light shines in
+    pub direction: Vector3<f32>,
+    /// Full cone angle in degrees
+    pub angle: f32,
+    /// Distance at which the light ends, the far plane of the shadow map
+    pub range: f32,
+    /// Slope scaled and constant depth offset applied while rendering, see `glPolygonOffset`. (2, 4) by default.
+    pub depth_offset: (f32, f32),
+    framebuffer: Framebuffer,
+    shader: ShaderProgram,
+    matrix: Matrix4<f32>,
+}
+impl SpotShadow {
+    /// Creates a `resolution`x`resolution` shadow map. Call `update` once the light is set up.
+    pub fn new(resolution: u32) -> Self {
+        let mut framebuffer = Framebuffer::new(resolution, resolution);
+        let depth = Texture::empty(resolution, resolution, TextureFormat::Depth32F, shadow_params());
+        framebuffer.attach_depth(Attachment::Texture(depth)).unwrap();
+        if let Err(err) = framebuffer.check() {
+            panic!("Error creating spot light shadow map: {}", err);
+        }
+        
+        let mut shadow = Self {
+            position: Vector3::new(0.0, 0.0, 0.0),
+            direction: Vector3::new(0.0, 0.0, -1.0),
+            angle: 45.0,
+            range: 50.0,
+            depth_offset: (2.0, 4.0),
+            framebuffer,
+            shader: depth_shader(),
+            matrix: Matrix4::from_scale(1.0),
+        };
+        shadow.update();
+        
+        shadow
+    }
+    
+    /// Recomputes the light's matrix after its fields changed.
+    pub fn update(&mut self) {
+        // the near plane only limits depth precision, so it's kept as far as the range allows
+        let near = (self.range * 0.001).max(0.01);
+        let projection = perspective(Deg(self.angle.clamp(1.0, 179.0)), 1.0, near, self.range);
+        let target = self.position + self.direction.normalize();
+        let view = Matrix4::look_at_rh(Point3::from_vec(self.position), Point3::from_vec(target), light_up(self.direction));
+        
+        self.matrix = projection * view;
+    }
+    
+    /// Renders the shadow map. `draw` is called with the bound depth shader and the light's view-projection
+    /// matrix, see `DirectionalShadow::render`.
+    pub fn render(&self, mut draw: impl FnMut(&ShaderProgram, Matrix4<f32>)) {
+        self.framebuffer.bind();
+        self.framebuffer.clear(1.0, 1.0, 1.0, 1.0);
+        let pass = DepthPass::begin(self.depth_offset, false);
+        
+        self.shader.bind();
+        draw(&self.shader, self.matrix);
+        self.shader.unbind();
+        
+        pass.end();
+        self.framebuffer.unbind();
+    }
+    
+    /// Binds the shadow map to texture unit `unit` and sets the uniforms of `shaders::SHADOW_SPOT_GLSL`.
+    /// `shader` must be bound.
+    pub fn bind(&self, shader: &ShaderProgram, unit: u32) {
+        self.depth_texture().bind_to(unit);
+        shader.set_uniform1i32("spotShadowMap", unit as i32);
+        shader.set_uniform_mat4f("spotShadowMatrix", texture_space() * self.matrix);
+    }
+    
+    /// The light's view-projection matrix, from world space to the shadow map's clip space
+    pub fn matrix(&self) -> Matrix4<f32> {
+        self.matrix
+    }
+    
+    /// Depth texture sampled with depth comparison
+    pub fn depth_texture(&self) -> &Texture {
+        self.framebuffer.depth().unwrap()
+    }
+}
@@ -0,0 +1,225 @@
+use std::rc::Rc;
+use cgmath::{vec3, vec4, InnerSpace, Matrix4, SquareMatrix, Vector3};
+use gl::types::*;
+use crate::camera::Camera;
+use crate::graphics::{GlTexture, ShaderProgram, Texture};
+use crate::ibl::Environment;
+use crate::shaders;
+use crate::texture::TextureCube;
+
+/// A sky fading from `horizon` up to `zenith` and down to `ground`, with a sun disk and glow. Colors are linear RGB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradientSky {
+    pub zenith: Vector3<f32>,
+    pub horizon: Vector3<f32>,
+    pub ground: Vector3<f32>,
+    pub sun: Vector3<f32>,
+    /// Direction towards the sun
+    pub sun_direction: Vector3<f32>,
+    /// Angular diameter of the sun disk in degrees
+    pub sun_size: f32,
+}
+impl Default for GradientSky {
+    fn default() -> Self {
+        Self {
+            zenith: vec3(0.1, 0.3, 0.8),
+            horizon: vec3(0.7, 0.8, 0.9),
+            ground: vec3(0.2, 0.2, 0.2),
+            sun: vec3(4.0, 3.8, 3.5),
+            sun_direction: vec3(0.3, 0.5, 0.4).normalize(),
+            sun_size: 1.0,
+        }
+    }
+}
+impl GradientSky {
+    pub fn with_colors(mut self, zenith: Vector3<f32>, horizon: Vector3<f32>, ground: Vector3<f32>) -> Self {
+        self.zenith = zenith;
+        self.horizon = horizon;
+        self.ground = ground;
+        
+        self
+    }
+    
+    pub fn with_sun(mut self, direction: Vector3<f32>, color: Vector3<f32>) -> Self {
+        self.sun_direction = direction.normalize();
+        self.sun = color;
+        
+        self
+    }
+}
+
+/// A sky computed by single scattering of sunlight through an Earth-like atmosphere, seen from the ground.
+/// Turns orange and dark as the sun sets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtmosphereSky {
+    /// Direction towards the sun
+    pub sun_direction: Vector3<f32>,
+    pub sun_intensity: f32,
+    /// Angular diameter of the sun disk in degrees
+    pub sun_size: f32,
+    /// Rayleigh scattering coefficients per meter, for red, green and blue
+    pub rayleigh: Vector3<f32>,
+    /// Mie scattering coefficient per meter, higher for hazier air
+    pub mie: f32,
+    /// Mie scattering anisotropy (-1 to 1), higher for a tighter halo around the sun
+    pub mie_g: f32,
+}
+impl Default for AtmosphereSky {
+    fn default() -> Self {
+        Self {
+            sun_direction: vec3(0.3, 0.5, 0.4).normalize(),
+            sun_intensity: 20.0,
+            sun_size: 1.0,
+            rayleigh: vec3(5.5e-6, 13.0e-6, 22.4e-6),
+            mie: 21e-6,
+            mie_g: 0.758,
+        }
+    }
+}
+impl AtmosphereSky {
+    pub fn with_sun(mut self, direction: Vector3<f32>, intensity: f32) -> Self {
+        self.sun_direction = direction.normalize();
+        self.sun_intensity = intensity;
+        
+        self
+    }
+}
+
+/// What a `Skybox` shows
+#[derive(Clone)]
+pub enum Sky {
+    Cube(Rc<TextureCube>),
+    /// The environment cube map of an `Environment`, matching its image based lighting
+    Environment(Rc<Environment>),
+    /// An equirectangular (latitude/longitude) panorama, e.g. from `ibl::load_hdr`
+    Equirectangular(Rc<Texture>),
+    Gradient(GradientSky),
+    Atmosphere(AtmosphereSky),
+}
+
+/// Draws a sky behind everything already rendered, as a fullscreen triangle at the far plane.
+///
+/// ```ignore
+/// let mut skybox = Skybox::new(Sky::Atmosphere(AtmosphereSky::default()));
+/// // after the opaque geometry, so the sky is only shaded where nothing else is
+/// skybox.render(&camera);
+/// ```
+///
+/// Relies on the depth test with `DepthFunc(LEQUAL)` set up by `Screen::new`, and a depth buffer cleared to 1.0.
+/// Doesn't write depth. With a `DeferredRenderer`, draw into its `output()` after `light`, which has the scene's
+/// depth. Colors are linear HDR, so render into an HDR target followed by tone mapping, or lower `intensity`.
+pub struct Skybox {
+    pub sky: Sky,
+    /// Multiplies the sky's color
+    pub intensity: f32,
+    shader: ShaderProgram,
+    vao: GLuint,
+}
+impl Skybox {
+    pub fn new(sky: Sky) -> Self {
+        let mut shader = ShaderProgram::new();
+        shader.create_vertex_shader(shaders::SKYBOX_VERT);
+        shader.create_fragment_shader(shaders::SKYBOX_FRAG);
+        shader.link();
+        
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        
+        Self {
+            sky,
+            intensity: 1.0,
+            shader,
+            vao,
+        }
+    }
+    
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        
+        self
+    }
+    
+    /// Points the sun of a procedural sky towards `direction`, e.g. to follow a `DirectionalLight` (which shines
+    /// the other way). Does nothing for textured skies.
+    pub fn set_sun_direction(&mut self, direction: Vector3<f32>) {
+        match &mut self.sky {
+            Sky::Gradient(sky) => sky.sun_direction = direction.normalize(),
+            Sky::Atmosphere(sky) => sky.sun_direction = direction.normalize(),
+            _ => (),
+        }
+    }
+    
+    /// Draws the sky as seen by `camera` into the bound framebuffer.
+    pub fn render(&self, camera: &Camera) {
+        let mut view = camera.view;
+        view.w = vec4(0.0, 0.0, 0.0, 1.0);
+        let inv_view_proj = (camera.projection * view).invert().unwrap_or_else(Matrix4::identity);
+        
+        let shader = &self.shader;
+        shader.bind();
+        shader.set_uniform_mat4f("invViewProj", inv_view_proj);
+        shader.set_uniform1f32("intensity", self.intensity);
+        // different sampler types can't share a unit
+        shader.set_uniform1i32("skyCube", 0);
+        shader.set_uniform1i32("skyPanorama", 1);
+        
+        let mode = match &self.sky {
+            Sky::Cube(texture) => {
+                texture.bind_to(0);
+                0
+            },
+            Sky::Environment(environment) => {
+                environment.environment().bind_to(0);
+                0
+            },
+            Sky::Equirectangular(texture) => {
+                texture.bind_to(1);
+                1
+            },
+            Sky::Gradient(sky) => {
+                let sun = sky.sun_direction.normalize();
+                shader.set_uniform3f32("zenithColor", sky.zenith.x, sky.zenith.y, sky.zenith.z);
+                shader.set_uniform3f32("horizonColor", sky.horizon.x, sky.horizon.y, sky.horizon.z);
+                shader.set_uniform3f32("groundColor", sky.ground.x, sky.ground.y, sky.ground.z);
+                shader.set_uniform3f32("sunColor", sky.sun.x, sky.sun.y, sky.sun.z);
+                shader.set_uniform3f32("sunDirection", sun.x, sun.y, sun.z);
+                shader.set_uniform1f32("sunSize", sky.sun_size);
+                2
+            },
+            Sky::Atmosphere(sky) => {
+                let sun = sky.sun_direction.normalize();
+                shader.set_uniform3f32("rayleigh", sky.rayleigh.x, sky.rayleigh.y, sky.rayleigh.z);
+                shader.set_uniform1f32("mie", sky.mie);
+                shader.set_uniform1f32("mieG", sky.mie_g);
+                shader.set_uniform1f32("sunIntensity", sky.sun_intensity);
+                shader.set_uniform3f32("sunDirection", sun.x, sun.y, sun.z);
+                shader.set_uniform1f32("sunSize", sky.sun_size);
+                3
+            },
+        };
+        shader.set_uniform1i32("skyMode", mode);
+        
+        unsafe {
+            let mut depth_mask = gl::TRUE;
+            gl::GetBooleanv(gl::DEPTH_WRITEMASK, &mut depth_mask);
+            gl::DepthMask(gl::FALSE);
+            
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+            
+            gl::DepthMask(depth_mask);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+        shader.unbind();
+    }
+}
+impl Drop for Skybox {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use crate::graphics::Texture;
+
+/// A rectangle of a texture, in pixels from the top-left corner (like the source image), along with the
+/// matching texture coordinates for `TextureRenderer`.
+///
+/// `v` is the coordinate of the region's bottom edge and `v2` its top edge, as textures are stored
+/// flipped vertically.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub u: f32,
+    pub v: f32,
+    pub u2: f32,
+    pub v2: f32,
+}
+impl TextureRegion {
+    pub fn new(tex: &Texture, x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self::from_size(tex.width, tex.height, x, y, width, height)
+    }
+    
+    /// Same as `new`, for a texture of `tex_width`x`tex_height` which may not be loaded yet.
+    pub fn from_size(tex_width: u32, tex_height: u32, x: u32, y: u32, width: u32, height: u32) -> Self {
+        let (tw, th) = (tex_width as f32, tex_height as f32);
+        
+        Self {
+            x,
+            y,
+            width,
+            height,
+            u: x as f32 / tw,
+            v: 1.0 - (y + height) as f32 / th,
+            u2: (x + width) as f32 / tw,
+            v2: 1.0 - y as f32 / th,
+        }
+    }
+    
+    /// The whole texture
+    pub fn full(tex: &Texture) -> Self {
+        Self::new(tex, 0, 0, tex.width, tex.height)
+    }
+    
+    /// Returns a copy with the texture coordinates mirrored horizontally and/or vertically.
+    pub fn flipped(&self, flip_x: bool, flip_y: bool) -> Self {
+        let mut region = *self;
+        if flip_x {
+            std::mem::swap(&mut region.u, &mut region.u2);
+        }
+        if flip_y {
+            std::mem::swap(&mut region.v, &mut region.v2);
+        }
+        
+        region
+    }
+}
+
+#[derive(Debug)]
+pub enum SpriteSheetError {
+    Io(PathBuf, std::io::Error),
+    Parse(serde_json::Error),
+    /// TexturePacker's rotated frames aren't supported; disable rotation when exporting.
+    RotatedFrame(String),
+}
+impl Display for SpriteSheetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpriteSheetError::Io(path, err) => write!(f, "Error reading sprite sheet {:?}: {}", path, err),
+            SpriteSheetError::Parse(err) => write!(f, "Error parsing sprite sheet: {}", err),
+            SpriteSheetError::RotatedFrame(name) => write!(f, "Frame {} is rotated, which isn't supported", name),
+        }
+    }
+}
+impl std::error::Error for SpriteSheetError {}
+
+/// A texture sliced into frames, either as a grid or from a TexturePacker/Aseprite JSON export.
+pub struct SpriteSheet {
+    pub texture: Texture,
+    pub frames: Vec<TextureRegion>,
+    /// Duration of each frame in milliseconds, if the file specified one (Aseprite)
+    pub durations: Vec<Option<u32>>,
+    /// Named frame ranges (Aseprite frame tags)
+    pub tags: HashMap<String, RangeInclusive<usize>>,
+    names: HashMap<String, usize>,
+}
+impl SpriteSheet {
+    /// Slices `texture` into `frame_width`x`frame_height` cells, left to right then top to bottom.
+    /// `margin` is the border around the whole grid, and `spacing` the gap between cells.
+    /// Panics if `frame_width` or `frame_height` is 0.
+    pub fn from_grid(texture: Texture, frame_width: u32, frame_height: u32, margin: u32, spacing: u32) -> Self {
+        if frame_width == 0 || frame_height == 0 {
+            panic!("SpriteSheet::from_grid needs a non-zero frame size, got {}x{}", frame_width, frame_height);
+        }
+        let columns = (texture.width.saturating_sub(margin * 2) + spacing) / (frame_width + spacing);
+        let rows = (texture.height.saturating_sub(margin * 2) + spacing) / (frame_height + spacing);
+        
+        let mut frames = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            for column in 0..columns {
+                let x = margin + column * (frame_width + spacing);
+                let y = margin + row * (frame_height + spacing);
+                frames.push(TextureRegion::new(&texture, x, y, frame_width, frame_height));
+            }
+        }
+        
+        Self {
+            texture,
+            durations: vec![None; frames.len()],
+            frames,
+            tags: HashMap::new(),
+            names: HashMap::new(),
+        }
+    }
+    
+    /// Loads a JSON sprite sheet and the image named in its `meta.image`, relative to the JSON file.
+    pub fn from_json_path(path: &Path) -> Result<Self, SpriteSheetError> {
+        let json = std::fs::read_to_string(path).map_err(|err| SpriteSheetError::Io(path.to_path_buf(), err))?;
+        let data: SheetData = serde_json::from_str(&json).map_err(SpriteSheetError::Parse)?;
+        
+        let image = path.parent().unwrap_or(Path::new("")).join(&data.meta.image);
+        let img = image::open(&image).map_err(|err| SpriteSheetError::Io(image.clone(), std::io::Error::other(err)))?;
+        
+        Self::from_data(Texture::from_image(img.into_rgba8()), data)
+    }
+    
+    /// Reads frames from a TexturePacker (hash or array) or Aseprite JSON export, for an already loaded texture.
+    pub fn from_json(texture: Texture, json: &str) -> Result<Self, SpriteSheetError> {
+        let data: SheetData = serde_json::from_str(json).map_err(SpriteSheetError::Parse)?;
+        
+        Self::from_data(texture, data)
+    }
+    
+    fn from_data(texture: Texture, data: SheetData) -> Result<Self, SpriteSheetError> {
+        let mut sheet = Self {
+            texture,
+            frames: Vec::with_capacity(data.frames.0.len()),
+            durations: Vec::with_capacity(data.frames.0.len()),
+            tags: HashMap::new(),
+            names: HashMap::new(),
+        };
+        
+        for (name, frame) in data.frames.0 {
+            if frame.rotated {
+                return Err(SpriteSheetError::RotatedFrame(name));
+            }
+            
+            let rect = frame.frame;
+            sheet.names.insert(name, sheet.frames.len());
+            sheet.frames.push(TextureRegion::new(&sheet.texture, rect.x, rect.y, rect.w, rect.h));
+            sheet.durations.push(frame.duration);
+        }
+        
+        for tag in data.meta.frame_tags {
+            sheet.tags.insert(tag.name, tag.from..=tag.to);
+        }
+        
+        Ok(sheet)
+    }
+    
+    pub fn frame(&self, index: usize) -> &TextureRegion {
+        &self.frames[index]
+    }
+    
+    /// Frame by its name in the JSON file, e.g. `"walk_01.png"`
+    pub fn get(&self, name: &str) -> Option<&TextureRegion> {
+        self.names.get(name).map(|i| &self.frames[*i])
+    }
+    
+    /// Frames of an Aseprite frame tag
+    pub fn tag(&self, name: &str) -> Option<&[TextureRegion]> {
+        self.tags.get(name).and_then(|range| self.frames.get(range.clone()))
+    }
+    
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+#[derive(Deserialize)]
+struct SheetData {
+    frames: Frames,
+    meta: Meta,
+}
+
+#[derive(Deserialize)]
+struct Meta {
+    #[serde(default)]
+    image: String,
+    #[serde(default, rename = "frameTags")]
+    frame_tags: Vec<FrameTag>,
+}
+
+#[derive(Deserialize)]
+struct FrameTag {
+    name: String,
+    from: usize,
+    to: usize,
+}
+
+#[derive(Deserialize)]
+struct Frame {
+    #[serde(default)]
+    filename: String,
+    frame: Rect,
+    #[serde(default)]
+    rotated: bool,
+    duration: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+/// Frames in file order, from either the hash (`{"name": {...}}`) or array (`[{"filename": "name", ...}]`) layout.
+/// Order matters since Aseprite frame tags refer to frames by index.
+struct Frames(Vec<(String, Frame)>);
+impl<'de> Deserialize<'de> for Frames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FramesVisitor;
+        impl<'de> Visitor<'de> for FramesVisitor {
+            type Value = Frames;
+            
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a map or array of frames")
+            }
+            
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Frames, A::Error> {
+                let mut frames = Vec::new();
+                while let Some((name, frame)) = map.next_entry::<String, Frame>()? {
+                    frames.push((name, frame));
+                }
+                
+                Ok(Frames(frames))
+            }
+            
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Frames, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(frame) = seq.next_element::<Frame>()? {
+                    frames.push((frame.filename.clone(), frame));
+                }
+                
+                Ok(Frames(frames))
+            }
+        }
+        
+        deserializer.deserialize_any(FramesVisitor)
+    }
+}
@@ -0,0 +1,301 @@
+use cgmath::{vec3, InnerSpace, Matrix4, SquareMatrix, Vector3};
+use gl::types::*;
+use crate::camera::Camera;
+use crate::deferred::GBuffer;
+use crate::framebuffer::{Attachment, Framebuffer, FramebufferError};
+use crate::graphics::{ShaderProgram, Texture, TextureFormat, TextureParams, Wrap};
+use crate::shaders;
+
+/// Most hemisphere samples per pixel, the size of `kernel` in `shaders::SSAO_FRAG`
+pub const MAX_SAMPLES: usize = 64;
+/// Size of the tiled random rotation texture, which the blur should cover
+const NOISE_SIZE: u32 = 4;
+
+/// Small deterministic generator for the kernel and noise, so results don't change between runs
+struct XorShift(u32);
+impl XorShift {
+    /// Uniform in 0-1
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+/// Sample offsets in the unit hemisphere around +Z, denser towards the center
+fn hemisphere_kernel(samples: usize) -> Vec<Vector3<f32>> {
+    let mut rng = XorShift(0x9E3779B9);
+    (0..samples).map(|i| {
+        let direction = vec3(rng.next() * 2.0 - 1.0, rng.next() * 2.0 - 1.0, rng.next()).normalize();
+        let t = i as f32 / samples as f32;
+        let scale = 0.1 + 0.9 * t * t;
+        
+        direction * rng.next() * scale
+    }).collect()
+}
+
+fn noise_texture() -> Texture {
+    let mut rng = XorShift(0x2545F491);
+    let data: Vec<f32> = (0..NOISE_SIZE * NOISE_SIZE * 2).map(|_| rng.next() * 2.0 - 1.0).collect();
+    
+    Texture::from_floats(NOISE_SIZE, NOISE_SIZE, TextureFormat::Rg16F, &data, TextureParams::default())
+}
+
+fn target(width: u32, height: u32) -> Result<Framebuffer, FramebufferError> {
+    let mut framebuffer = Framebuffer::new(width, height);
+    framebuffer.attach_color(Attachment::Texture(Texture::empty(width, height, TextureFormat::R8, TextureParams::linear().with_wrap(Wrap::ClampToEdge))))?;
+    framebuffer.check()?;
+    
+    Ok(framebuffer)
+}
+
+fn fullscreen_shader(fragment: &str) -> ShaderProgram {
+    let mut shader = ShaderProgram::new();
+    shader.create_vertex_shader(shaders::FULLSCREEN_VERT);
+    shader.create_fragment_shader(fragment);
+    shader.link();
+    
+    shader
+}
+
+/// Screen space ambient occlusion, computed from the depth and world space normals of an offscreen render.
+///
+/// ```ignore
+/// deferred.end();
+/// ssao.compute_gbuffer(deferred.gbuffer(), &camera);
+/// deferred.light_with_occlusion(&camera, &lights, ssao.occlusion());
+/// ```
+///
+/// Each pixel tests `samples` points in the hemisphere around its normal, randomly rotated per pixel by a tiled
+/// noise texture, against the depth buffer; a depth aware blur then removes the noise pattern without
+/// smudging edges. For forward rendering, use `compute` with the scene's depth and normal textures, then
+/// `apply` to darken the lit result, or `bind` to use the occlusion in a custom shader.
+pub struct Ssao {
+    /// Size of the sampled hemisphere in world units
+    pub radius: f32,
+    /// Depth difference below which samples don't count as occluded, against self-shadowing acne
+    pub bias: f32,
+    /// Exponent applied to the result, higher for darker occlusion
+    pub power: f32,
+    /// How strongly depth edges stop the blur
+    pub blur_sharpness: f32,
+    /// How much `apply` darkens the target, from 0 (not at all) to 1
+    pub strength: f32,
+    kernel: Vec<Vector3<f32>>,
+    noise: Texture,
+    occlusion: Framebuffer,
+    blurred: Framebuffer,
+    ssao_shader: ShaderProgram,
+    blur_shader: ShaderProgram,
+    composite_shader: ShaderProgram,
+    vao: GLuint,
+}
+impl Ssao {
+    /// Creates the occlusion targets at `width` x `height`, which should match the size of the input textures.
+    pub fn new(width: u32, height: u32) -> Result<Self, FramebufferError> {
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        
+        let ssao = Self {
+            radius: 0.5,
+            bias: 0.025,
+            power: 1.0,
+            blur_sharpness: 400.0,
+            strength: 1.0,
+            kernel: hemisphere_kernel(32),
+            noise: noise_texture(),
+            occlusion: target(width, height)?,
+            blurred: target(width, height)?,
+            ssao_shader: fullscreen_shader(shaders::SSAO_FRAG),
+            blur_shader: fullscreen_shader(shaders::SSAO_BLUR_FRAG),
+            composite_shader: fullscreen_shader(shaders::SSAO_COMPOSITE_FRAG),
+            vao,
+        };
+        ssao.upload_kernel();
+        
+        Ok(ssao)
+    }
+    
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        
+        self
+    }
+    
+    pub fn with_bias(mut self, bias: f32) -> Self {
+        self.bias = bias;
+        
+        self
+    }
+    
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.set_samples(samples);
+        
+        self
+    }
+    
+    /// Changes the number of hemisphere samples per pixel, 32 by default. Fewer is faster but noisier.
+    pub fn set_samples(&mut self, samples: usize) {
+        if samples == 0 || samples > MAX_SAMPLES {
+            panic!("SSAO sample count must be between 1 and {}, got {}", MAX_SAMPLES, samples);
+        }
+        self.kernel = hemisphere_kernel(samples);
+        self.upload_kernel();
+    }
+    
+    pub fn samples(&self) -> usize {
+        self.kernel.len()
+    }
+    
+    /// Recreates the occlusion targets at the new size, e.g. when the window is resized.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        self.occlusion.resize(width, height)?;
+        self.blurred.resize(width, height)
+    }
+    
+    /// Computes the occlusion from a depth texture and world space normals (RGB), both rendered by `camera`.
+    pub fn compute(&self, depth: &Texture, normals: &Texture, camera: &Camera) {
+        let (width, height) = (self.occlusion.width as f32, self.occlusion.height as f32);
+        let inv_projection = camera.projection.invert().unwrap_or(Matrix4::identity());
+        
+        let (depth_test, blend) = unsafe { (gl::IsEnabled(gl::DEPTH_TEST), gl::IsEnabled(gl::BLEND)) };
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::BLEND);
+        }
+        
+        let shader = &self.ssao_shader;
+        self.occlusion.bind();
+        shader.bind();
+        depth.bind_to(0);
+        normals.bind_to(1);
+        self.noise.bind_to(2);
+        shader.set_uniform1i32("depthMap", 0);
+        shader.set_uniform1i32("normalMap", 1);
+        shader.set_uniform1i32("noiseMap", 2);
+        shader.set_uniform2f32("noiseScale", width / NOISE_SIZE as f32, height / NOISE_SIZE as f32);
+        shader.set_uniform_mat4f("projection", camera.projection);
+        shader.set_uniform_mat4f("invProjection", inv_projection);
+        shader.set_uniform_mat4f("view", camera.view);
+        shader.set_uniform1f32("radius", self.radius);
+        shader.set_uniform1f32("bias", self.bias);
+        shader.set_uniform1f32("power", self.power);
+        self.fullscreen();
+        self.occlusion.unbind();
+        
+        // horizontally into `blurred`, then vertically back into `occlusion`
+        let shader = &self.blur_shader;
+        shader.bind();
+        depth.bind_to(1);
+        shader.set_uniform1i32("occlusionMap", 0);
+        shader.set_uniform1i32("depthMap", 1);
+        shader.set_uniform_mat4f("invProjection", inv_projection);
+        shader.set_uniform1f32("sharpness", self.blur_sharpness);
+        for (source, target, direction) in [(&self.occlusion, &self.blurred, (1.0 / width, 0.0)), (&self.blurred, &self.occlusion, (0.0, 1.0 / height))] {
+            target.bind();
+            source.color(0).bind_to(0);
+            shader.set_uniform2f32("direction", direction.0, direction.1);
+            self.fullscreen();
+            target.unbind();
+        }
+        shader.unbind();
+        
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            if depth_test == gl::TRUE {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+            if blend == gl::TRUE {
+                gl::Enable(gl::BLEND);
+            }
+        }
+    }
+    
+    /// Computes the occlusion from the depth and normals of a `DeferredRenderer`'s G-buffer.
+    pub fn compute_gbuffer(&self, gbuffer: &GBuffer, camera: &Camera) {
+        self.compute(gbuffer.depth(), gbuffer.normals(), camera);
+    }
+    
+    /// Multiplies the color of `target` by the occlusion, scaled by `strength`. This darkens direct light as
+    /// well as ambient light; with a `DeferredRenderer`, prefer `light_with_occlusion`.
+    pub fn apply(&self, target: &Framebuffer) {
+        let (depth_test, blend) = unsafe { (gl::IsEnabled(gl::DEPTH_TEST), gl::IsEnabled(gl::BLEND)) };
+        let mut blend_func = [0; 4];
+        let mut blend_equation = [0; 2];
+        unsafe {
+            for (i, param) in [gl::BLEND_SRC_RGB, gl::BLEND_DST_RGB, gl::BLEND_SRC_ALPHA, gl::BLEND_DST_ALPHA].into_iter().enumerate() {
+                gl::GetIntegerv(param, &mut blend_func[i]);
+            }
+            gl::GetIntegerv(gl::BLEND_EQUATION_RGB, &mut blend_equation[0]);
+            gl::GetIntegerv(gl::BLEND_EQUATION_ALPHA, &mut blend_equation[1]);
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Enable(gl::BLEND);
+            gl::BlendEquation(gl::FUNC_ADD);
+            gl::BlendFuncSeparate(gl::ZERO, gl::SRC_COLOR, gl::ZERO, gl::ONE);
+        }
+        
+        target.bind();
+        self.composite_shader.bind();
+        self.bind(&self.composite_shader, 0);
+        self.composite_shader.set_uniform1f32("strength", self.strength);
+        self.fullscreen();
+        self.composite_shader.unbind();
+        target.unbind();
+        
+        unsafe {
+            let [src_rgb, dst_rgb, src_alpha, dst_alpha] = blend_func.map(|func| func as GLenum);
+            gl::BlendFuncSeparate(src_rgb, dst_rgb, src_alpha, dst_alpha);
+            gl::BlendEquationSeparate(blend_equation[0] as GLenum, blend_equation[1] as GLenum);
+            if blend == gl::FALSE {
+                gl::Disable(gl::BLEND);
+            }
+            if depth_test == gl::TRUE {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+        }
+    }
+    
+    /// Binds the occlusion to unit `unit` and sets the `occlusionMap` sampler of `shader`, which must be bound.
+    pub fn bind(&self, shader: &ShaderProgram, unit: u32) {
+        self.occlusion().bind_to(unit);
+        shader.set_uniform1i32("occlusionMap", unit as i32);
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+    
+    /// The blurred occlusion of the last `compute` in R, 1 where unoccluded
+    pub fn occlusion(&self) -> &Texture {
+        self.occlusion.color(0)
+    }
+    
+    /// Sets the kernel uniforms, which keep their values until the sample count changes
+    fn upload_kernel(&self) {
+        let shader = &self.ssao_shader;
+        shader.bind();
+        for (i, sample) in self.kernel.iter().enumerate() {
+            shader.set_uniform3f32(&format!("kernel[{}]", i), sample.x, sample.y, sample.z);
+        }
+        shader.set_uniform1i32("sampleCount", self.kernel.len() as i32);
+        shader.unbind();
+    }
+    
+    fn fullscreen(&self) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+        }
+    }
+}
+impl Drop for Ssao {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
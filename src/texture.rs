@@ -0,0 +1,462 @@
+use std::f32::consts::PI;
+use std::ffi::c_void;
+use std::path::PathBuf;
+use gl::types::*;
+use image::RgbaImage;
+use crate::graphics::{update_params, GlTexture, MipmapMode, TextureFormat, TextureParams};
+
+/// Generates and binds a texture object for `target`, with `params` applied.
+fn gen(target: GLenum, params: &TextureParams) -> GLuint {
+    let mut id = 0;
+    unsafe {
+        gl::GenTextures(1, &mut id);
+        gl::BindTexture(target, id);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+    }
+    params.apply(target);
+    
+    id
+}
+
+/// Finishes a texture started by `gen()`.
+fn finish(target: GLenum, params: &TextureParams) {
+    unsafe {
+        if params.mipmap != MipmapMode::None {
+            gl::GenerateMipmap(target);
+        }
+        gl::BindTexture(target, 0);
+    }
+}
+
+fn check_len(len: usize, expected: usize, what: &str) {
+    if len != expected {
+        panic!("Expected {} bytes for {}, got {}", expected, what, len);
+    }
+}
+
+fn data_ptr(data: Option<&[u8]>) -> *const c_void {
+    data.map(|d| d.as_ptr() as *const c_void).unwrap_or(std::ptr::null())
+}
+
+/// Cube map texture. Faces are ordered +X, -X, +Y, -Y, +Z, -Z, matching `TEXTURE_CUBE_MAP_POSITIVE_X + i`.
+///
+/// Unlike `Texture`, face images are uploaded without flipping, as cube maps use a top-left origin.
+#[derive(Debug)]
+pub struct TextureCube {
+    id: GLuint,
+    pub size: u32,
+    format: TextureFormat,
+    params: TextureParams,
+}
+impl TextureCube {
+    pub fn from_paths(paths: &[PathBuf; 6], params: TextureParams) -> Self {
+        let faces = paths.clone().map(|path| image::open(path).unwrap().into_rgba8());
+        
+        Self::from_images(&faces, params)
+    }
+    
+    pub fn from_images(faces: &[RgbaImage; 6], params: TextureParams) -> Self {
+        let size = faces[0].width();
+        for face in faces {
+            if face.width() != size || face.height() != size {
+                panic!("Cube map faces must be square and the same size, expected {0}x{0} but got {1}x{2}", size, face.width(), face.height());
+            }
+        }
+        
+        Self::from_bytes(size, TextureFormat::Rgba8, faces.each_ref().map(|face| face.as_raw().as_slice()), params)
+    }
+    
+    /// Creates a cube map by sampling an equirectangular (latitude/longitude) panorama.
+    pub fn from_equirectangular(panorama: &RgbaImage, face_size: u32, params: TextureParams) -> Self {
+        let faces: [RgbaImage; 6] = std::array::from_fn(|face| {
+            RgbaImage::from_fn(face_size, face_size, |x, y| {
+                let s = 2.0 * (x as f32 + 0.5) / face_size as f32 - 1.0;
+                let t = 2.0 * (y as f32 + 0.5) / face_size as f32 - 1.0;
+                let (dx, dy, dz) = face_direction(face, s, t);
+                
+                let len = (dx * dx + dy * dy + dz * dz).sqrt();
+                let u = 0.5 + dz.atan2(dx) / (2.0 * PI);
+                let v = 0.5 - (dy / len).asin() / PI;
+                
+                sample_bilinear(panorama, u, v)
+            })
+        });
+        
+        Self::from_images(&faces, params)
+    }
+    
+    /// Creates a cube map from raw pixel data for each face, laid out as described by `format`.
+    pub fn from_bytes(size: u32, format: TextureFormat, faces: [&[u8]; 6], params: TextureParams) -> Self {
+        let face_len = (size * size) as usize * format.bytes_per_pixel();
+        for face in faces {
+            check_len(face.len(), face_len, "a cube map face");
+        }
+        
+        Self::gl_gen(size, format, Some(faces), params)
+    }
+    
+    /// Creates a cube map with undefined contents, e.g. as a render target.
+    pub fn empty(size: u32, format: TextureFormat, params: TextureParams) -> Self {
+        Self::gl_gen(size, format, None, params)
+    }
+    
+    fn gl_gen(size: u32, format: TextureFormat, faces: Option<[&[u8]; 6]>, params: TextureParams) -> Self {
+        let id = gen(gl::TEXTURE_CUBE_MAP, &params);
+        unsafe {
+            gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+            
+            for i in 0..6 {
+                let data = data_ptr(faces.map(|faces| faces[i]));
+                gl::TexImage2D(gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as GLenum, 0, format.internal_format() as GLint, size as GLsizei, size as GLsizei, 0, format.format(), format.data_type(), data);
+            }
+        }
+        finish(gl::TEXTURE_CUBE_MAP, &params);
+        
+        Self {
+            id,
+            size,
+            format,
+            params,
+        }
+    }
+    
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+}
+impl GlTexture for TextureCube {
+    fn id(&self) -> GLuint {
+        self.id
+    }
+    
+    fn target(&self) -> GLenum {
+        gl::TEXTURE_CUBE_MAP
+    }
+    
+    fn params(&self) -> &TextureParams {
+        &self.params
+    }
+    
+    fn set_params(&mut self, params: TextureParams) {
+        update_params(gl::TEXTURE_CUBE_MAP, self.id, &self.params, &params);
+        self.params = params;
+    }
+}
+impl Drop for TextureCube {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+/// Direction through face coordinates `s`/`t` (each -1..1) of cube map face `face`
+pub(crate) fn face_direction(face: usize, s: f32, t: f32) -> (f32, f32, f32) {
+    match face {
+        0 => (1.0, -t, -s),
+        1 => (-1.0, -t, s),
+        2 => (s, 1.0, t),
+        3 => (s, -1.0, -t),
+        4 => (s, -t, 1.0),
+        _ => (-s, -t, -1.0),
+    }
+}
+
+fn sample_bilinear(img: &RgbaImage, u: f32, v: f32) -> image::Rgba<u8> {
+    let (w, h) = (img.width() as i64, img.height() as i64);
+    let x = u * w as f32 - 0.5;
+    let y = (v * h as f32 - 0.5).clamp(0.0, (h - 1) as f32);
+    let (x0, y0) = (x.floor() as i64, y.floor() as i64);
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    
+    // wraps horizontally, since the panorama's left and right edges meet
+    let texel = |x: i64, y: i64| img.get_pixel(x.rem_euclid(w) as u32, y.clamp(0, h - 1) as u32).0;
+    let (a, b, c, d) = (texel(x0, y0), texel(x0 + 1, y0), texel(x0, y0 + 1), texel(x0 + 1, y0 + 1));
+    
+    let mut out = [0u8; 4];
+    for i in 0..4 {
+        let top = a[i] as f32 * (1.0 - fx) + b[i] as f32 * fx;
+        let bottom = c[i] as f32 * (1.0 - fx) + d[i] as f32 * fx;
+        out[i] = (top * (1.0 - fy) + bottom * fy).round() as u8;
+    }
+    
+    image::Rgba(out)
+}
+
+/// Volume texture, e.g. for color grading LUTs or volume data.
+#[derive(Debug)]
+pub struct Texture3D {
+    id: GLuint,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    format: TextureFormat,
+    params: TextureParams,
+}
+impl Texture3D {
+    /// Creates a texture from raw pixel data, ordered by x, then y, then z (slice).
+    pub fn from_bytes(width: u32, height: u32, depth: u32, format: TextureFormat, data: &[u8], params: TextureParams) -> Self {
+        check_len(data.len(), (width * height * depth) as usize * format.bytes_per_pixel(), "a 3D texture");
+        
+        Self::gl_gen(width, height, depth, format, Some(data), params)
+    }
+    
+    /// Creates a color grading LUT from an unwrapped strip image of `n` slices, each `n`x`n` pixels and
+    /// placed left to right (so the image is `n*n` wide and `n` tall). Red increases along x within a
+    /// slice, green along y (downwards), and blue with each slice.
+    pub fn from_lut_image(img: &RgbaImage, params: TextureParams) -> Self {
+        let n = img.height();
+        if img.width() != n * n {
+            panic!("LUT image must be {}x{} for {} slices, got {}x{}", n * n, n, n, img.width(), img.height());
+        }
+        
+        let mut data = Vec::with_capacity((n * n * n * 4) as usize);
+        for b in 0..n {
+            for g in 0..n {
+                for r in 0..n {
+                    data.extend_from_slice(&img.get_pixel(b * n + r, g).0);
+                }
+            }
+        }
+        
+        Self::from_bytes(n, n, n, TextureFormat::Rgba8, &data, params.with_wrap(crate::graphics::Wrap::ClampToEdge))
+    }
+    
+    /// Creates a texture with undefined contents, e.g. to be written by a compute shader.
+    pub fn empty(width: u32, height: u32, depth: u32, format: TextureFormat, params: TextureParams) -> Self {
+        Self::gl_gen(width, height, depth, format, None, params)
+    }
+    
+    fn gl_gen(width: u32, height: u32, depth: u32, format: TextureFormat, data: Option<&[u8]>, params: TextureParams) -> Self {
+        let id = gen(gl::TEXTURE_3D, &params);
+        unsafe {
+            gl::TexImage3D(gl::TEXTURE_3D, 0, format.internal_format() as GLint, width as GLsizei, height as GLsizei, depth as GLsizei, 0, format.format(), format.data_type(), data_ptr(data));
+        }
+        finish(gl::TEXTURE_3D, &params);
+        
+        Self {
+            id,
+            width,
+            height,
+            depth,
+            format,
+            params,
+        }
+    }
+    
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+}
+impl GlTexture for Texture3D {
+    fn id(&self) -> GLuint {
+        self.id
+    }
+    
+    fn target(&self) -> GLenum {
+        gl::TEXTURE_3D
+    }
+    
+    fn params(&self) -> &TextureParams {
+        &self.params
+    }
+    
+    fn set_params(&mut self, params: TextureParams) {
+        update_params(gl::TEXTURE_3D, self.id, &self.params, &params);
+        self.params = params;
+    }
+}
+impl Drop for Texture3D {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+/// Array of same-sized 2D layers, sampled with `sampler2DArray` and a layer index, e.g. for sprite
+/// layers or terrain splat maps. Layers are flipped vertically like `Texture`.
+#[derive(Debug)]
+pub struct Texture2DArray {
+    id: GLuint,
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+    format: TextureFormat,
+    params: TextureParams,
+}
+impl Texture2DArray {
+    pub fn from_paths(paths: &[PathBuf], params: TextureParams) -> Self {
+        let images: Vec<RgbaImage> = paths.iter().map(|path| image::open(path).unwrap().into_rgba8()).collect();
+        
+        Self::from_images(&images, params)
+    }
+    
+    pub fn from_images(images: &[RgbaImage], params: TextureParams) -> Self {
+        let (width, height) = images.first().map(|img| img.dimensions()).unwrap_or((0, 0));
+        
+        let mut arr = Self::empty(width, height, images.len() as u32, TextureFormat::Rgba8, TextureParams { mipmap: MipmapMode::None, ..params });
+        for (layer, img) in images.iter().enumerate() {
+            arr.set_layer(layer as u32, img);
+        }
+        arr.set_params(params);
+        
+        arr
+    }
+    
+    /// Creates a texture from raw pixel data for all layers, each with rows ordered top to bottom.
+    pub fn from_bytes(width: u32, height: u32, layers: u32, format: TextureFormat, data: &[u8], params: TextureParams) -> Self {
+        let row = width as usize * format.bytes_per_pixel();
+        check_len(data.len(), row * (height * layers) as usize, "a 2D array texture");
+        
+        let flipped: Vec<u8> = data.chunks_exact(row * height as usize).flat_map(|layer| layer.chunks_exact(row).rev().flatten()).copied().collect();
+        
+        Self::gl_gen(width, height, layers, format, Some(&flipped), params)
+    }
+    
+    /// Creates a texture with undefined contents, to be filled with `set_layer()` or rendered to.
+    pub fn empty(width: u32, height: u32, layers: u32, format: TextureFormat, params: TextureParams) -> Self {
+        Self::gl_gen(width, height, layers, format, None, params)
+    }
+    
+    fn gl_gen(width: u32, height: u32, layers: u32, format: TextureFormat, data: Option<&[u8]>, params: TextureParams) -> Self {
+        let id = gen(gl::TEXTURE_2D_ARRAY, &params);
+        unsafe {
+            gl::TexImage3D(gl::TEXTURE_2D_ARRAY, 0, format.internal_format() as GLint, width as GLsizei, height as GLsizei, layers as GLsizei, 0, format.format(), format.data_type(), data_ptr(data));
+        }
+        finish(gl::TEXTURE_2D_ARRAY, &params);
+        
+        Self {
+            id,
+            width,
+            height,
+            layers,
+            format,
+            params,
+        }
+    }
+    
+    /// Replaces the contents of `layer`. The texture must be `Rgba8`, and `img` the same size as the layers.
+    pub fn set_layer(&mut self, layer: u32, img: &RgbaImage) {
+        if self.format != TextureFormat::Rgba8 {
+            panic!("Texture2DArray::set_layer requires an Rgba8 texture, this one is {:?}", self.format);
+        }
+        if img.dimensions() != (self.width, self.height) || layer >= self.layers {
+            panic!("Layer {} ({}x{}) doesn't fit a {}x{} array of {} layers", layer, img.width(), img.height(), self.width, self.height, self.layers);
+        }
+        
+        let img = image::imageops::flip_vertical(img);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage3D(gl::TEXTURE_2D_ARRAY, 0, 0, 0, layer as GLint, self.width as GLsizei, self.height as GLsizei, 1, gl::RGBA, gl::UNSIGNED_BYTE, img.as_raw().as_ptr() as *const c_void);
+            if self.params.mipmap != MipmapMode::None {
+                gl::GenerateMipmap(gl::TEXTURE_2D_ARRAY);
+            }
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+    }
+    
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+}
+impl GlTexture for Texture2DArray {
+    fn id(&self) -> GLuint {
+        self.id
+    }
+    
+    fn target(&self) -> GLenum {
+        gl::TEXTURE_2D_ARRAY
+    }
+    
+    fn params(&self) -> &TextureParams {
+        &self.params
+    }
+    
+    fn set_params(&mut self, params: TextureParams) {
+        update_params(gl::TEXTURE_2D_ARRAY, self.id, &self.params, &params);
+        self.params = params;
+    }
+}
+impl Drop for Texture2DArray {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+/// Multisampled 2D texture, used as a framebuffer attachment and read in shaders with `sampler2DMS` and
+/// `texelFetch`. It can't be filtered, so `params` are kept but never applied; resolve it into a regular
+/// `Texture` with `Framebuffer::resolve_to` to sample it normally.
+#[derive(Debug)]
+pub struct Texture2DMultisample {
+    id: GLuint,
+    pub width: u32,
+    pub height: u32,
+    pub samples: u32,
+    format: TextureFormat,
+    params: TextureParams,
+}
+impl Texture2DMultisample {
+    /// `samples` is clamped to the driver's maximum for `format`.
+    pub fn new(width: u32, height: u32, format: TextureFormat, samples: u32) -> Self {
+        let samples = max_samples(format).min(samples).max(1);
+        
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D_MULTISAMPLE, id);
+            gl::TexImage2DMultisample(gl::TEXTURE_2D_MULTISAMPLE, samples as GLsizei, format.internal_format(), width as GLsizei, height as GLsizei, gl::TRUE);
+            gl::BindTexture(gl::TEXTURE_2D_MULTISAMPLE, 0);
+        }
+        
+        Self {
+            id,
+            width,
+            height,
+            samples,
+            format,
+            params: TextureParams::default(),
+        }
+    }
+    
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+}
+impl GlTexture for Texture2DMultisample {
+    fn id(&self) -> GLuint {
+        self.id
+    }
+    
+    fn target(&self) -> GLenum {
+        gl::TEXTURE_2D_MULTISAMPLE
+    }
+    
+    fn params(&self) -> &TextureParams {
+        &self.params
+    }
+    
+    fn set_params(&mut self, params: TextureParams) {
+        self.params = params;
+    }
+}
+impl Drop for Texture2DMultisample {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+/// Highest sample count the driver supports for multisampled textures and renderbuffers of `format`
+pub fn max_samples(format: TextureFormat) -> u32 {
+    let mut max = 0;
+    unsafe {
+        gl::GetInternalformativ(gl::TEXTURE_2D_MULTISAMPLE, format.internal_format(), gl::SAMPLES, 1, &mut max);
+    }
+    
+    max.max(1) as u32
+}